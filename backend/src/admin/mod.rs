@@ -0,0 +1,173 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, error, warn};
+use crate::geoip::GeoIpService;
+use crate::geoip_updater::GeoIpUpdater;
+use crate::parser_updater::ParserUpdater;
+use crate::site_config;
+use crate::channel;
+use crate::revenue;
+
+/// Shared state for the authenticated admin endpoints
+#[derive(Clone)]
+pub struct AdminState {
+    pub admin_token: Arc<String>,
+    pub geoip_service: GeoIpService,
+    pub geoip_updaters: Vec<Arc<GeoIpUpdater>>,
+    pub parser_updater: Arc<ParserUpdater>,
+    pub site_config_path: Option<PathBuf>,
+    pub channel_rules_path: PathBuf,
+    pub exchange_rates_path: Option<PathBuf>,
+}
+
+/// Admin routes, all protected by a bearer token
+pub fn router(state: AdminState) -> Router {
+    Router::new()
+        .route("/admin/geoip/status", get(geoip_status))
+        .route("/admin/geoip/update", post(geoip_update))
+        .route("/admin/geoip/reload", post(geoip_reload))
+        .route("/admin/parsers/reload", post(parsers_reload))
+        .route("/admin/site-config/reload", post(site_config_reload))
+        .route("/admin/exchange-rates/reload", post(exchange_rates_reload))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin_token))
+        .with_state(state)
+}
+
+/// Reloads the GeoIP databases from disk whenever the process receives SIGHUP
+#[cfg(unix)]
+pub fn spawn_sighup_handler(geoip_service: GeoIpService) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!("Failed to install SIGHUP handler: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading GeoIP databases from disk");
+            if let Err(e) = geoip_service.reload_from_disk() {
+                error!("Failed to reload GeoIP databases: {:#}", e);
+            }
+        }
+    });
+}
+
+async fn require_admin_token(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if tokens_match(token, &state.admin_token) => next.run(request).await,
+        _ => {
+            warn!("Rejected unauthenticated admin request to {}", request.uri().path());
+            (StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+        }
+    }
+}
+
+/// Constant-time token comparison
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn geoip_status_body(state: &AdminState) -> serde_json::Value {
+    json!({
+        "service": state.geoip_service.status(),
+        "updaters": state.geoip_updaters.iter().map(|updater| updater.status()).collect::<Vec<_>>(),
+    })
+}
+
+async fn geoip_status(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(geoip_status_body(&state))
+}
+
+/// Runs an update check immediately and applies any new database without waiting for the reader check interval
+async fn geoip_update(State(state): State<AdminState>) -> impl IntoResponse {
+    info!("Admin triggered GeoIP database update");
+    // Updaters without lookups enabled or without a source report "disabled" or "not_configured" instead
+    for updater in state.geoip_updaters.iter().filter(|updater| updater.is_configured()) {
+        updater.check_and_update().await;
+    }
+    state.geoip_service.force_refresh();
+
+    Json(geoip_status_body(&state))
+}
+
+async fn geoip_reload(State(state): State<AdminState>) -> impl IntoResponse {
+    info!("Admin triggered GeoIP database reload from disk");
+    match state.geoip_service.reload_from_disk() {
+        Ok(()) => (StatusCode::OK, Json(geoip_status_body(&state))),
+        Err(e) => {
+            error!("Failed to reload GeoIP databases: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": format!("{:#}", e) })))
+        }
+    }
+}
+
+/// Reloads the user agent regexes and referrer database from disk
+async fn parsers_reload(State(state): State<AdminState>) -> impl IntoResponse {
+    info!("Admin triggered parser data reload from disk");
+    let results = state.parser_updater.reload_all();
+    let status = if results.iter().all(|result| result.reloaded) {
+        StatusCode::OK
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    (status, Json(json!({ "parsers": results })))
+}
+
+/// Reloads the default channel rules and the per-site settings file, if configured.
+/// The previous rules and settings stay active if a new file is invalid.
+async fn site_config_reload(State(state): State<AdminState>) -> impl IntoResponse {
+    info!("Admin triggered channel rules and site config reload");
+    let result = channel::reload(&state.channel_rules_path)
+        .and_then(|()| match state.site_config_path.as_deref() {
+            Some(site_config_path) => site_config::reload(site_config_path),
+            None => Ok(()),
+        });
+
+    match result {
+        Ok(()) => (StatusCode::OK, Json(json!({ "reloaded": true }))),
+        Err(e) => {
+            error!("Failed to reload site config: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": format!("{:#}", e) })))
+        }
+    }
+}
+
+async fn exchange_rates_reload(State(state): State<AdminState>) -> impl IntoResponse {
+    let Some(exchange_rates_path) = state.exchange_rates_path.as_deref() else {
+        return (StatusCode::NOT_FOUND, Json(json!({ "error": "EXCHANGE_RATES_PATH is not configured" })));
+    };
+
+    info!("Admin triggered exchange rates reload from {:?}", exchange_rates_path);
+    match revenue::reload(exchange_rates_path) {
+        Ok(()) => (StatusCode::OK, Json(json!({ "reloaded": true }))),
+        Err(e) => {
+            error!("Failed to reload exchange rates: {:#}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": format!("{:#}", e) })))
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Classification of a client IP address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpClass {
+    Public,
+    Loopback,
+    /// RFC 1918, IPv4 link-local, IPv6 unique local (fc00::/7) and link-local addresses
+    Private,
+    /// Carrier-grade NAT shared address space (100.64.0.0/10)
+    Cgnat,
+    /// Unspecified, multicast, documentation, benchmarking and other non-routable ranges
+    Reserved,
+}
+
+impl IpClass {
+    /// Sentinel country code used instead of a geolocation lookup for non-public addresses
+    pub fn sentinel_code(&self) -> Option<&'static str> {
+        match self {
+            IpClass::Public => None,
+            IpClass::Loopback => Some("Localhost"),
+            IpClass::Private => Some("Private"),
+            IpClass::Cgnat => Some("CGNAT"),
+            IpClass::Reserved => Some("Reserved"),
+        }
+    }
+}
+
+/// Normalize an IP address so that IPv4-mapped IPv6 addresses (e.g. `::ffff:1.2.3.4`) become plain IPv4
+pub fn normalize_ip(ip: IpAddr) -> IpAddr {
+    ip.to_canonical()
+}
+
+/// Classify a normalized IP address
+pub fn classify_ip(ip: &IpAddr) -> IpClass {
+    match ip {
+        IpAddr::V4(ipv4) => classify_ipv4(ipv4),
+        IpAddr::V6(ipv6) => classify_ipv6(ipv6),
+    }
+}
+
+fn classify_ipv4(ip: &Ipv4Addr) -> IpClass {
+    let octets = ip.octets();
+
+    if ip.is_loopback() {
+        IpClass::Loopback
+    } else if ip.is_private() || ip.is_link_local() {
+        IpClass::Private
+    } else if octets[0] == 100 && (octets[1] & 0b1100_0000) == 64 {
+        IpClass::Cgnat
+    } else if ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || octets[0] == 0
+        || octets[0] >= 240
+        || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0)
+        || (octets[0] == 198 && (octets[1] & 0xfe) == 18)
+    {
+        IpClass::Reserved
+    } else {
+        IpClass::Public
+    }
+}
+
+fn classify_ipv6(ip: &Ipv6Addr) -> IpClass {
+    let segments = ip.segments();
+
+    if ip.is_loopback() {
+        IpClass::Loopback
+    } else if ip.is_unique_local() || ip.is_unicast_link_local() {
+        IpClass::Private
+    } else if ip.is_unspecified()
+        || ip.is_multicast()
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+    {
+        IpClass::Reserved
+    } else {
+        IpClass::Public
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(ip: &str) -> IpClass {
+        classify_ip(&normalize_ip(ip.parse().unwrap()))
+    }
+
+    #[test]
+    fn classifies_ipv4_ranges() {
+        let cases = [
+            ("127.0.0.1", IpClass::Loopback),
+            ("10.1.2.3", IpClass::Private),
+            ("172.16.0.1", IpClass::Private),
+            ("192.168.1.1", IpClass::Private),
+            ("169.254.0.1", IpClass::Private),
+            ("169.254.255.255", IpClass::Private),
+            ("100.63.255.255", IpClass::Public),
+            ("100.64.0.0", IpClass::Cgnat),
+            ("100.127.255.255", IpClass::Cgnat),
+            ("100.128.0.0", IpClass::Public),
+            ("0.0.0.0", IpClass::Reserved),
+            ("0.1.2.3", IpClass::Reserved),
+            ("192.0.2.1", IpClass::Reserved),
+            ("198.18.0.1", IpClass::Reserved),
+            ("224.0.0.1", IpClass::Reserved),
+            ("255.255.255.255", IpClass::Reserved),
+            ("8.8.8.8", IpClass::Public),
+        ];
+
+        for (ip, expected) in cases {
+            assert_eq!(classify(ip), expected, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn classifies_ipv6_ranges() {
+        let cases = [
+            ("::1", IpClass::Loopback),
+            ("::ffff:127.0.0.1", IpClass::Loopback),
+            ("::ffff:10.0.0.1", IpClass::Private),
+            ("::ffff:100.64.0.1", IpClass::Cgnat),
+            ("::ffff:8.8.8.8", IpClass::Public),
+            ("fc00::1", IpClass::Private),
+            ("fdff:ffff::1", IpClass::Private),
+            ("fe80::1", IpClass::Private),
+            ("febf::1", IpClass::Private),
+            ("fec0::1", IpClass::Public),
+            ("::", IpClass::Reserved),
+            ("ff02::1", IpClass::Reserved),
+            ("2001:db8::1", IpClass::Reserved),
+            ("2606:4700:4700::1111", IpClass::Public),
+        ];
+
+        for (ip, expected) in cases {
+            assert_eq!(classify(ip), expected, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn only_public_addresses_fall_through_to_geoip_lookup() {
+        assert_eq!(normalize_ip("::ffff:1.2.3.4".parse().unwrap()), "1.2.3.4".parse::<IpAddr>().unwrap());
+
+        assert_eq!(classify("8.8.8.8").sentinel_code(), None);
+        assert_eq!(classify("2606:4700:4700::1111").sentinel_code(), None);
+        assert_eq!(classify("127.0.0.1").sentinel_code(), Some("Localhost"));
+        assert_eq!(classify("192.168.0.1").sentinel_code(), Some("Private"));
+        assert_eq!(classify("100.64.0.1").sentinel_code(), Some("CGNAT"));
+        assert_eq!(classify("0.0.0.0").sentinel_code(), Some("Reserved"));
+    }
+}
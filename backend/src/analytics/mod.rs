@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
 use nanoid::nanoid;
+use std::net::IpAddr;
+use crate::ua_parser::ClientHints;
 
 mod fingerprint;
+mod ip;
 pub use fingerprint::*;
+pub use ip::{IpClass, classify_ip, normalize_ip};
 
 /// Raw tracking data received from the client
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,6 +21,9 @@ pub struct RawTrackingEvent {
     pub properties: String,
     /// Page URL
     pub url: String,
+    /// Page title (document.title)
+    #[serde(default)]
+    pub title: Option<String>,
     /// Referrer URL
     pub referrer: Option<String>,
     /// User agent
@@ -25,6 +32,15 @@ pub struct RawTrackingEvent {
     pub screen_resolution: String,
     /// Timestamp of the event
     pub timestamp: u64,
+    /// User-Agent Client Hints collected by the tracker, merged with `Sec-CH-UA-*` request headers
+    #[serde(default)]
+    pub client_hints: Option<ClientHints>,
+    /// Revenue of a purchase or other e-commerce event, validated during processing so a malformed value only drops the revenue
+    #[serde(default)]
+    pub revenue: Option<serde_json::Value>,
+    /// Signed token from a link on another domain of the same site, used to continue the visitor and session
+    #[serde(default)]
+    pub cross_domain_token: Option<String>,
 }
 
 /// The main analytics event type that includes server-side data
@@ -32,15 +48,15 @@ pub struct RawTrackingEvent {
 pub struct AnalyticsEvent {
     /// Raw tracking data from the client
     pub raw: RawTrackingEvent,
-    /// Client IP address
-    pub ip_address: String,
+    /// Client IP address, normalized so IPv4-mapped IPv6 addresses are plain IPv4
+    pub ip_address: IpAddr,
 }
 
 impl AnalyticsEvent {
-    pub fn new(raw: RawTrackingEvent, ip_address: String) -> Self {
+    pub fn new(raw: RawTrackingEvent, ip_address: IpAddr) -> Self {
         Self {
             raw,
-            ip_address,
+            ip_address: normalize_ip(ip_address),
         }
     }
 }
@@ -1,4 +1,6 @@
-use url::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use url::{Url, form_urlencoded};
 
 /// Campaign tracking information
 #[derive(Debug, Clone, Default)]
@@ -8,27 +10,301 @@ pub struct CampaignInfo {
     pub utm_campaign: Option<String>,
     pub utm_term: Option<String>,
     pub utm_content: Option<String>,
+    pub utm_id: Option<String>,
+    pub utm_source_platform: Option<String>,
+    pub utm_creative_format: Option<String>,
+    pub utm_marketing_tactic: Option<String>,
+    /// Ad platform identified from a click ID parameter (e.g. "google_ads"). The ID itself is never stored.
+    pub click_id_platform: Option<String>,
 }
 
-/// Parse campaign parameters from a URL
-pub fn parse_campaign_params(url_str: &str) -> CampaignInfo {
+/// Campaign fields that query parameters can be mapped onto
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignField {
+    Source,
+    Medium,
+    Campaign,
+    Term,
+    Content,
+    Id,
+    SourcePlatform,
+    CreativeFormat,
+    MarketingTactic,
+}
+
+impl CampaignInfo {
+    fn field_mut(&mut self, field: CampaignField) -> &mut Option<String> {
+        match field {
+            CampaignField::Source => &mut self.utm_source,
+            CampaignField::Medium => &mut self.utm_medium,
+            CampaignField::Campaign => &mut self.utm_campaign,
+            CampaignField::Term => &mut self.utm_term,
+            CampaignField::Content => &mut self.utm_content,
+            CampaignField::Id => &mut self.utm_id,
+            CampaignField::SourcePlatform => &mut self.utm_source_platform,
+            CampaignField::CreativeFormat => &mut self.utm_creative_format,
+            CampaignField::MarketingTactic => &mut self.utm_marketing_tactic,
+        }
+    }
+
+    /// Source and medium implied by the click ID platform, for channel grouping of events without UTM parameters
+    pub fn click_id_source_medium(&self) -> Option<(&'static str, Option<&'static str>)> {
+        let platform = self.click_id_platform.as_deref()?;
+        CLICK_ID_PARAMS
+            .iter()
+            .find(|click_id| click_id.platform == platform)
+            .map(|click_id| (click_id.source, click_id.medium))
+    }
+}
+
+/// Per-site campaign parameter settings
+#[derive(Debug, Clone, Deserialize)]
+pub struct CampaignSettings {
+    /// Extra parameter names mapped onto campaign fields (e.g. `{"newsletter": "source"}`).
+    /// These rank below `utm_*` parameters but above the built-in aliases.
+    #[serde(default)]
+    pub aliases: HashMap<String, CampaignField>,
+    #[serde(default = "default_trim")]
+    pub trim: bool,
+    #[serde(default)]
+    pub lowercase: bool,
+}
+
+fn default_trim() -> bool {
+    true
+}
+
+impl Default for CampaignSettings {
+    fn default() -> Self {
+        Self {
+            aliases: HashMap::new(),
+            trim: default_trim(),
+            lowercase: false,
+        }
+    }
+}
+
+impl CampaignSettings {
+    fn normalize(&self, value: &str) -> Option<String> {
+        let value = if self.trim { value.trim() } else { value };
+        if value.is_empty() {
+            return None;
+        }
+        Some(if self.lowercase { value.to_lowercase() } else { value.to_string() })
+    }
+
+    /// The campaign field a parameter maps onto and its precedence (lower wins)
+    fn resolve(&self, key: &str) -> Option<(CampaignField, u8)> {
+        if let Some(field) = self.aliases.get(key) {
+            return Some((*field, SITE_ALIAS_PRIORITY));
+        }
+        STANDARD_PARAMS
+            .iter()
+            .find(|(name, _, _)| *name == key)
+            .map(|(_, field, priority)| (*field, *priority))
+    }
+}
+
+const SITE_ALIAS_PRIORITY: u8 = 1;
+
+/// Built-in campaign parameters with their precedence: `utm_*`, then Matomo `mtm_*`, then Piwik `pk_*`,
+/// then generic `ref`/`source` parameters. Site aliases rank directly below `utm_*`.
+const STANDARD_PARAMS: &[(&str, CampaignField, u8)] = &[
+    ("utm_source", CampaignField::Source, 0),
+    ("utm_medium", CampaignField::Medium, 0),
+    ("utm_campaign", CampaignField::Campaign, 0),
+    ("utm_term", CampaignField::Term, 0),
+    ("utm_content", CampaignField::Content, 0),
+    ("utm_id", CampaignField::Id, 0),
+    ("utm_source_platform", CampaignField::SourcePlatform, 0),
+    ("utm_creative_format", CampaignField::CreativeFormat, 0),
+    ("utm_marketing_tactic", CampaignField::MarketingTactic, 0),
+    ("mtm_source", CampaignField::Source, 2),
+    ("mtm_medium", CampaignField::Medium, 2),
+    ("mtm_campaign", CampaignField::Campaign, 2),
+    ("mtm_keyword", CampaignField::Term, 2),
+    ("mtm_content", CampaignField::Content, 2),
+    ("mtm_cid", CampaignField::Id, 2),
+    ("pk_source", CampaignField::Source, 3),
+    ("pk_medium", CampaignField::Medium, 3),
+    ("pk_campaign", CampaignField::Campaign, 3),
+    ("pk_cpn", CampaignField::Campaign, 3),
+    ("pk_keyword", CampaignField::Term, 3),
+    ("pk_kwd", CampaignField::Term, 3),
+    ("pk_content", CampaignField::Content, 3),
+    ("pk_cid", CampaignField::Id, 3),
+    ("ref", CampaignField::Source, 4),
+    ("source", CampaignField::Source, 4),
+];
+
+/// A click ID parameter appended by an ad platform, with the source and medium it implies
+struct ClickIdParam {
+    param: &'static str,
+    platform: &'static str,
+    source: &'static str,
+    /// `None` when the parameter does not imply a paid click
+    medium: Option<&'static str>,
+}
+
+/// Known click ID parameters, in order of precedence when several are present
+const CLICK_ID_PARAMS: &[ClickIdParam] = &[
+    ClickIdParam { param: "gclid", platform: "google_ads", source: "google", medium: Some("cpc") },
+    ClickIdParam { param: "gbraid", platform: "google_ads", source: "google", medium: Some("cpc") },
+    ClickIdParam { param: "wbraid", platform: "google_ads", source: "google", medium: Some("cpc") },
+    ClickIdParam { param: "dclid", platform: "google_display", source: "google", medium: Some("display") },
+    ClickIdParam { param: "msclkid", platform: "microsoft_ads", source: "bing", medium: Some("cpc") },
+    ClickIdParam { param: "ttclid", platform: "tiktok_ads", source: "tiktok", medium: Some("paid_social") },
+    ClickIdParam { param: "li_fat_id", platform: "linkedin_ads", source: "linkedin", medium: Some("paid_social") },
+    ClickIdParam { param: "twclid", platform: "x_ads", source: "twitter", medium: Some("paid_social") },
+    // Facebook also appends fbclid to organic shares, so it does not mark the visit as paid
+    ClickIdParam { param: "fbclid", platform: "meta", source: "facebook", medium: None },
+];
+
+/// Parse campaign parameters from a URL, including parameters in the fragment (e.g. `#/page?utm_source=x`)
+pub fn parse_campaign_params(url_str: &str, settings: &CampaignSettings) -> CampaignInfo {
     let url = match Url::parse(url_str) {
         Ok(url) => url,
         Err(_) => return CampaignInfo::default(),
     };
     
     let mut campaign_info = CampaignInfo::default();
+    let mut priorities: HashMap<CampaignField, u8> = HashMap::new();
+    let mut click_id_index: Option<usize> = None;
+
+    // Fragment parameters come first so that query parameters of the same precedence replace them
+    let fragment_pairs = url.fragment()
+        .and_then(fragment_query)
+        .map(|query| form_urlencoded::parse(query.as_bytes()).into_owned().collect::<Vec<_>>())
+        .unwrap_or_default();
     
-    for (key, value) in url.query_pairs() {
-        match key.as_ref() {
-            "utm_source" => campaign_info.utm_source = Some(value.to_string()),
-            "utm_medium" => campaign_info.utm_medium = Some(value.to_string()),
-            "utm_campaign" => campaign_info.utm_campaign = Some(value.to_string()),
-            "utm_term" => campaign_info.utm_term = Some(value.to_string()),
-            "utm_content" => campaign_info.utm_content = Some(value.to_string()),
-            _ => {}
+    for (key, value) in fragment_pairs.into_iter().chain(url.query_pairs().into_owned()) {
+        if let Some(index) = CLICK_ID_PARAMS.iter().position(|p| p.param == key && !value.is_empty()) {
+            click_id_index = Some(click_id_index.map_or(index, |current| current.min(index)));
+        }
+
+        let Some((field, priority)) = settings.resolve(&key) else { continue };
+        let Some(value) = settings.normalize(&value) else { continue };
+
+        if priorities.get(&field).is_none_or(|current| priority <= *current) {
+            *campaign_info.field_mut(field) = Some(value);
+            priorities.insert(field, priority);
         }
     }
+
+    // Only the platform is stored; the channel step derives a source and medium from it when UTMs are absent
+    if let Some(click_id) = click_id_index.map(|index| &CLICK_ID_PARAMS[index]) {
+        campaign_info.click_id_platform = Some(click_id.platform.to_string());
+    }
     
     campaign_info
-}
\ No newline at end of file
+}
+
+/// The query string part of a URL fragment used by hash-based routers
+fn fragment_query(fragment: &str) -> Option<&str> {
+    match fragment.split_once('?') {
+        Some((_, query)) => Some(query),
+        None if fragment.contains('=') => Some(fragment),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> CampaignInfo {
+        parse_campaign_params(url, &CampaignSettings::default())
+    }
+
+    #[test]
+    fn applies_parameter_precedence() {
+        let settings = CampaignSettings {
+            aliases: HashMap::from([("newsletter".to_string(), CampaignField::Source)]),
+            ..Default::default()
+        };
+        let parse_with_alias = |url: &str| parse_campaign_params(url, &settings).utm_source;
+
+        assert_eq!(parse_with_alias("https://example.com/?ref=a&pk_source=b&mtm_source=c&newsletter=d&utm_source=e").as_deref(), Some("e"));
+        assert_eq!(parse_with_alias("https://example.com/?utm_source=e&newsletter=d").as_deref(), Some("e"));
+        assert_eq!(parse_with_alias("https://example.com/?ref=a&pk_source=b&mtm_source=c&newsletter=d").as_deref(), Some("d"));
+        assert_eq!(parse_with_alias("https://example.com/?ref=a&pk_source=b&mtm_source=c").as_deref(), Some("c"));
+        assert_eq!(parse_with_alias("https://example.com/?source=a&pk_source=b").as_deref(), Some("b"));
+        assert_eq!(parse_with_alias("https://example.com/?source=a").as_deref(), Some("a"));
+
+        // Without the site alias the parameter is ignored
+        assert_eq!(parse("https://example.com/?newsletter=d").utm_source, None);
+
+        let info = parse("https://example.com/?pk_cpn=spring&mtm_keyword=shoes&pk_kwd=boots");
+        assert_eq!(info.utm_campaign.as_deref(), Some("spring"));
+        assert_eq!(info.utm_term.as_deref(), Some("shoes"));
+    }
+
+    #[test]
+    fn normalizes_values() {
+        let url = "https://example.com/?utm_source=%20Google%20&utm_medium=CPC";
+        let info = parse(url);
+        assert_eq!(info.utm_source.as_deref(), Some("Google"));
+        assert_eq!(info.utm_medium.as_deref(), Some("CPC"));
+
+        let lowercase = CampaignSettings { lowercase: true, ..Default::default() };
+        let info = parse_campaign_params(url, &lowercase);
+        assert_eq!(info.utm_source.as_deref(), Some("google"));
+        assert_eq!(info.utm_medium.as_deref(), Some("cpc"));
+
+        let untrimmed = CampaignSettings { trim: false, ..Default::default() };
+        assert_eq!(parse_campaign_params(url, &untrimmed).utm_source.as_deref(), Some(" Google "));
+    }
+
+    #[test]
+    fn ignores_empty_values() {
+        let info = parse("https://example.com/?utm_source=&utm_medium=%20%20&mtm_source=matomo&gclid=");
+        assert_eq!(info.utm_source.as_deref(), Some("matomo"));
+        assert_eq!(info.utm_medium, None);
+        assert_eq!(info.click_id_platform, None);
+    }
+
+    #[test]
+    fn parses_fragment_parameters() {
+        let info = parse("https://example.com/#/pricing?utm_source=twitter&utm_campaign=launch");
+        assert_eq!(info.utm_source.as_deref(), Some("twitter"));
+        assert_eq!(info.utm_campaign.as_deref(), Some("launch"));
+
+        assert_eq!(parse("https://example.com/#utm_source=twitter").utm_source.as_deref(), Some("twitter"));
+        assert_eq!(parse("https://example.com/#pricing").utm_source, None);
+
+        // Query parameters of the same precedence replace fragment parameters
+        let info = parse("https://example.com/?utm_source=google#/pricing?utm_source=twitter&utm_medium=social");
+        assert_eq!(info.utm_source.as_deref(), Some("google"));
+        assert_eq!(info.utm_medium.as_deref(), Some("social"));
+
+        // but a higher precedence fragment parameter still wins over a lower precedence query parameter
+        let info = parse("https://example.com/?ref=newsletter#/pricing?utm_source=twitter");
+        assert_eq!(info.utm_source.as_deref(), Some("twitter"));
+    }
+
+    #[test]
+    fn picks_click_id_by_precedence() {
+        let info = parse("https://example.com/?fbclid=abc&gclid=def");
+        assert_eq!(info.click_id_platform.as_deref(), Some("google_ads"));
+        assert_eq!(info.click_id_source_medium(), Some(("google", Some("cpc"))));
+
+        let info = parse("https://example.com/?fbclid=abc&msclkid=def#/page?gclid=ghi");
+        assert_eq!(info.click_id_platform.as_deref(), Some("google_ads"));
+
+        let info = parse("https://example.com/?ttclid=abc&dclid=def");
+        assert_eq!(info.click_id_source_medium(), Some(("google", Some("display"))));
+
+        // Click IDs are never stored as campaign values
+        assert_eq!(parse("https://example.com/?gclid=def").utm_source, None);
+        assert_eq!(parse("https://example.com/").click_id_source_medium(), None);
+    }
+
+    #[test]
+    fn fbclid_does_not_mark_the_visit_as_paid() {
+        let info = parse("https://example.com/?fbclid=abc");
+        assert_eq!(info.click_id_platform.as_deref(), Some("meta"));
+        assert_eq!(info.click_id_source_medium(), Some(("facebook", None)));
+        assert_eq!(info.utm_medium, None);
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{Result, Context};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tracing::{info, error};
+use url::Url;
+use crate::campaign::CampaignInfo;
+use crate::referrer::ReferrerInfo;
+
+/// Channel reported when no rule matches
+pub const UNASSIGNED_CHANNEL: &str = "Unassigned";
+
+/// A channel rule as written in the rules file. All present conditions must match.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChannelRuleDefinition {
+    pub channel: String,
+    /// Referrer source categories (e.g. "search", "social", "direct")
+    #[serde(default)]
+    pub referrer_source: Vec<String>,
+    /// Exact source names, matched against utm_source or the referrer name/host
+    #[serde(default)]
+    pub source: Vec<String>,
+    pub source_regex: Option<String>,
+    /// Exact utm_medium values
+    #[serde(default)]
+    pub medium: Vec<String>,
+    pub medium_regex: Option<String>,
+    pub campaign_regex: Option<String>,
+    /// Query parameters of which at least one must be present on the landing URL (e.g. click IDs)
+    #[serde(default)]
+    pub query_params: Vec<String>,
+    /// Whether the landing URL must (true) or must not (false) carry UTM parameters
+    pub has_campaign: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChannelRulesFile {
+    rules: Vec<ChannelRuleDefinition>,
+}
+
+#[derive(Debug)]
+struct ChannelRule {
+    channel: String,
+    referrer_source: HashSet<String>,
+    source: HashSet<String>,
+    source_regex: Option<Regex>,
+    medium: HashSet<String>,
+    medium_regex: Option<Regex>,
+    campaign_regex: Option<Regex>,
+    query_params: Vec<String>,
+    has_campaign: Option<bool>,
+}
+
+/// Values a landing event is classified on, lowercased once up front
+struct ChannelInput<'a> {
+    referrer_source: &'a str,
+    source: Option<String>,
+    medium: Option<String>,
+    campaign: Option<String>,
+    query_params: HashSet<String>,
+    has_campaign: bool,
+}
+
+/// An ordered list of channel rules; the first matching rule wins
+#[derive(Debug, Default)]
+pub struct ChannelRuleSet {
+    rules: Vec<ChannelRule>,
+}
+
+impl ChannelRuleSet {
+    pub fn compile(definitions: Vec<ChannelRuleDefinition>) -> Result<Self> {
+        let compile_regex = |pattern: Option<String>| -> Result<Option<Regex>> {
+            pattern
+                .map(|p| Regex::new(&format!("(?i){}", p)).with_context(|| format!("Invalid channel rule regex '{}'", p)))
+                .transpose()
+        };
+        let lowercase_set = |values: Vec<String>| values.into_iter().map(|v| v.to_lowercase()).collect();
+
+        let rules = definitions
+            .into_iter()
+            .map(|definition| {
+                Ok(ChannelRule {
+                    channel: definition.channel,
+                    referrer_source: lowercase_set(definition.referrer_source),
+                    source: lowercase_set(definition.source),
+                    source_regex: compile_regex(definition.source_regex)?,
+                    medium: lowercase_set(definition.medium),
+                    medium_regex: compile_regex(definition.medium_regex)?,
+                    campaign_regex: compile_regex(definition.campaign_regex)?,
+                    query_params: definition.query_params,
+                    has_campaign: definition.has_campaign,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { rules })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    fn classify(&self, input: &ChannelInput) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(input))
+            .map(|rule| rule.channel.as_str())
+    }
+}
+
+impl ChannelRule {
+    fn matches(&self, input: &ChannelInput) -> bool {
+        let matches_value = |set: &HashSet<String>, regex: &Option<Regex>, value: Option<&str>| {
+            if set.is_empty() && regex.is_none() {
+                return true;
+            }
+            value.is_some_and(|value| {
+                set.contains(value) || regex.as_ref().is_some_and(|regex| regex.is_match(value))
+            })
+        };
+
+        (self.referrer_source.is_empty() || self.referrer_source.contains(input.referrer_source))
+            && matches_value(&self.source, &self.source_regex, input.source.as_deref())
+            && matches_value(&self.medium, &self.medium_regex, input.medium.as_deref())
+            && self.campaign_regex.as_ref().is_none_or(|regex| input.campaign.as_deref().is_some_and(|c| regex.is_match(c)))
+            && (self.query_params.is_empty() || self.query_params.iter().any(|param| input.query_params.contains(param)))
+            && self.has_campaign.is_none_or(|has_campaign| has_campaign == input.has_campaign)
+    }
+}
+
+static DEFAULT_RULES: Lazy<RwLock<Arc<ChannelRuleSet>>> = Lazy::new(|| RwLock::new(Arc::new(ChannelRuleSet::default())));
+
+pub fn initialize(channel_rules_path: &Path) {
+    info!("Initializing channel rules from: {:?}", channel_rules_path);
+
+    if let Err(e) = reload(channel_rules_path) {
+        error!("Could not load channel rules from {:?}: {:#}. All events will be reported as \"{}\".", channel_rules_path, e, UNASSIGNED_CHANNEL);
+    }
+}
+
+/// Load and swap in the default channel rules; the previous rules stay active if the new file is invalid or empty
+pub fn reload(channel_rules_path: &Path) -> Result<()> {
+    let rules = load_channel_rules(channel_rules_path)?;
+    anyhow::ensure!(!rules.is_empty(), "No channel rules in {:?}", channel_rules_path);
+
+    info!("Loaded {} channel rules", rules.len());
+    *DEFAULT_RULES.write().unwrap() = Arc::new(rules);
+    Ok(())
+}
+
+pub fn load_channel_rules(channel_rules_path: &Path) -> Result<ChannelRuleSet> {
+    let contents = fs::read_to_string(channel_rules_path)
+        .with_context(|| format!("Failed to read channel rules {:?}", channel_rules_path))?;
+    let file: ChannelRulesFile = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse channel rules {:?}", channel_rules_path))?;
+    ChannelRuleSet::compile(file.rules)
+}
+
+/// Classify an event into a marketing channel. Site specific rules are evaluated before the default rules.
+pub fn classify_channel(
+    referrer_info: &ReferrerInfo,
+    campaign_info: &CampaignInfo,
+    landing_url: &str,
+    site_rules: Option<&ChannelRuleSet>,
+) -> String {
+    let lowercase = |value: Option<&String>| value.filter(|v| !v.is_empty()).map(|v| v.to_lowercase());
+
+    // Sanitized referrer URLs have no scheme, so the host is everything before the first slash
+    let referrer_host = referrer_info.url.as_deref()
+        .and_then(|url| url.split('/').next())
+        .map(|host| host.to_string());
+
+    // Click IDs imply a default source and medium (e.g. gclid: google / cpc); explicit UTM parameters take precedence
+    let (click_id_source, click_id_medium) = campaign_info.click_id_source_medium().unzip();
+
+    let input = ChannelInput {
+        referrer_source: referrer_info.source_type.as_str(),
+        source: lowercase(campaign_info.utm_source.as_ref())
+            .or_else(|| click_id_source.map(str::to_string))
+            .or_else(|| lowercase(referrer_info.source_name.as_ref()))
+            .or_else(|| lowercase(referrer_host.as_ref())),
+        medium: lowercase(campaign_info.utm_medium.as_ref())
+            .or_else(|| click_id_medium.flatten().map(str::to_string)),
+        campaign: lowercase(campaign_info.utm_campaign.as_ref()),
+        query_params: Url::parse(landing_url)
+            .map(|url| url.query_pairs().map(|(key, _)| key.into_owned()).collect())
+            .unwrap_or_default(),
+        has_campaign: campaign_info.utm_source.is_some()
+            || campaign_info.utm_medium.is_some()
+            || campaign_info.utm_campaign.is_some()
+            || click_id_medium.flatten().is_some(),
+    };
+
+    let default_rules = DEFAULT_RULES.read().unwrap().clone();
+
+    site_rules
+        .and_then(|rules| rules.classify(&input))
+        .or_else(|| default_rules.classify(&input))
+        .unwrap_or(UNASSIGNED_CHANNEL)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::referrer::ReferrerSource;
+
+    const LANDING_URL: &str = "https://shop.example/products";
+
+    fn load_default_rules() {
+        reload(Path::new("assets/channels/default_channels.json")).unwrap();
+    }
+
+    fn referrer(source_type: ReferrerSource, source_name: Option<&str>, url: Option<&str>) -> ReferrerInfo {
+        ReferrerInfo {
+            url: url.map(str::to_string),
+            source_type,
+            source_name: source_name.map(str::to_string),
+            search_term: None,
+        }
+    }
+
+    fn campaign(source: Option<&str>, medium: Option<&str>) -> CampaignInfo {
+        CampaignInfo {
+            utm_source: source.map(str::to_string),
+            utm_medium: medium.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classifies_with_default_rules() {
+        load_default_rules();
+        let direct = ReferrerInfo::default();
+        let classify = |referrer: &ReferrerInfo, campaign: &CampaignInfo, url: &str| classify_channel(referrer, campaign, url, None);
+
+        assert_eq!(classify(&direct, &CampaignInfo::default(), "https://shop.example/?gclid=abc"), "Paid Search");
+        assert_eq!(classify(&direct, &campaign(Some("Google"), Some("CPC")), LANDING_URL), "Paid Search");
+        assert_eq!(
+            classify(&referrer(ReferrerSource::Search, Some("Google"), Some("www.google.com/search")), &campaign(None, Some("ppc")), LANDING_URL),
+            "Paid Search"
+        );
+        assert_eq!(
+            classify(&referrer(ReferrerSource::Search, Some("Google"), Some("www.google.com/search")), &CampaignInfo::default(), LANDING_URL),
+            "Organic Search"
+        );
+        assert_eq!(
+            classify(&referrer(ReferrerSource::Social, Some("Facebook"), Some("m.facebook.com/")), &CampaignInfo::default(), LANDING_URL),
+            "Organic Social"
+        );
+        assert_eq!(classify(&direct, &campaign(Some("linkedin"), Some("social")), LANDING_URL), "Organic Social");
+        assert_eq!(classify(&direct, &campaign(Some("spring_sale"), Some("Email")), LANDING_URL), "Email");
+        assert_eq!(classify(&referrer(ReferrerSource::Email, Some("Gmail"), None), &CampaignInfo::default(), LANDING_URL), "Email");
+        assert_eq!(classify(&direct, &CampaignInfo::default(), LANDING_URL), "Direct");
+    }
+
+    #[test]
+    fn site_rules_take_precedence_over_default_rules() {
+        load_default_rules();
+        let site_rules = ChannelRuleSet::compile(serde_json::from_str(r#"[
+            { "channel": "Partners", "source": ["partner-portal"] },
+            { "channel": "Newsletter", "medium_regex": "^news" }
+        ]"#).unwrap()).unwrap();
+
+        let classify = |campaign: &CampaignInfo| classify_channel(&ReferrerInfo::default(), campaign, LANDING_URL, Some(&site_rules));
+
+        assert_eq!(classify(&campaign(Some("Partner-Portal"), Some("referral"))), "Partners");
+        // Would be "Email" under the default rules
+        assert_eq!(classify(&campaign(Some("weekly"), Some("newsletter"))), "Newsletter");
+        // No site rule matches, so the default rules apply
+        assert_eq!(classify(&campaign(Some("google"), Some("cpc"))), "Paid Search");
+    }
+
+    #[test]
+    fn falls_back_to_unassigned() {
+        load_default_rules();
+        let classify = |referrer: &ReferrerInfo, campaign: &CampaignInfo| classify_channel(referrer, campaign, LANDING_URL, None);
+
+        // An unknown medium on a campaign link matches no rule
+        assert_eq!(classify(&ReferrerInfo::default(), &campaign(Some("partner"), Some("qr_code"))), UNASSIGNED_CHANNEL);
+        assert_eq!(
+            classify(&referrer(ReferrerSource::Other, None, Some("blog.example/post")), &campaign(Some("blog"), Some("qr_code"))),
+            UNASSIGNED_CHANNEL
+        );
+
+        let empty_rules = ChannelRuleSet::default();
+        assert_eq!(empty_rules.classify(&ChannelInput {
+            referrer_source: "direct",
+            source: None,
+            medium: None,
+            campaign: None,
+            query_params: HashSet::new(),
+            has_campaign: false,
+        }), None);
+    }
+
+    #[test]
+    fn click_ids_imply_source_and_medium_without_utm_parameters() {
+        load_default_rules();
+        let site_rules = ChannelRuleSet::compile(serde_json::from_str(r#"[
+            { "channel": "Google CPC", "source": ["google"], "medium": ["cpc"] },
+            { "channel": "Facebook", "source": ["facebook"], "has_campaign": false }
+        ]"#).unwrap()).unwrap();
+        let click_id = |platform: &str| CampaignInfo { click_id_platform: Some(platform.to_string()), ..Default::default() };
+
+        // The click ID itself is not on the landing URL, only its platform is known
+        assert_eq!(classify_channel(&ReferrerInfo::default(), &click_id("google_ads"), LANDING_URL, Some(&site_rules)), "Google CPC");
+        assert_eq!(classify_channel(&ReferrerInfo::default(), &click_id("google_ads"), LANDING_URL, None), "Paid Search");
+        assert_eq!(classify_channel(&ReferrerInfo::default(), &click_id("tiktok_ads"), LANDING_URL, None), "Paid Social");
+
+        // fbclid implies a source but no medium, so the visit is neither paid nor a campaign
+        assert_eq!(classify_channel(&ReferrerInfo::default(), &click_id("meta"), LANDING_URL, Some(&site_rules)), "Facebook");
+        assert_eq!(classify_channel(&ReferrerInfo::default(), &click_id("meta"), LANDING_URL, None), "Direct");
+
+        // Explicit UTM parameters take precedence over the implied ones
+        let mut tagged = click_id("google_ads");
+        tagged.utm_medium = Some("email".to_string());
+        assert_eq!(classify_channel(&ReferrerInfo::default(), &tagged, LANDING_URL, None), "Email");
+    }
+}
@@ -1,6 +1,49 @@
 use std::env;
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::time::Duration;
+use crate::revenue;
+
+/// What to do with events whose referrer is on the referrer spam list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReferrerSpamAction {
+    /// Discard the event entirely
+    #[default]
+    Drop,
+    /// Keep the event but record its referrer source as "spam"
+    Flag,
+}
+
+impl ReferrerSpamAction {
+    fn from_env(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "flag" => Self::Flag,
+            _ => Self::Drop,
+        }
+    }
+}
+
+/// How much autonomous system detail is stored per event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsnPrivacy {
+    /// Store both the AS number and the organization name
+    #[default]
+    Full,
+    /// Store only the organization name
+    Organization,
+    /// Do not store any ASN data
+    Off,
+}
+
+impl AsnPrivacy {
+    fn from_env(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "organization" => Self::Organization,
+            "off" => Self::Off,
+            _ => Self::Full,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Config {
@@ -14,16 +57,50 @@ pub struct Config {
     pub enable_geolocation: bool,
     pub maxmind_account_id: Option<String>,
     pub maxmind_license_key: Option<String>,
+    pub geoip_edition: String,
     pub geoip_db_path: PathBuf,
     pub geoip_update_interval: Duration,
+    pub geoip_source: String,
+    pub geoip_source_url: Option<String>,
+    pub geoip_source_checksum_url: Option<String>,
+    pub geoip_source_auth: String,
+    pub geoip_source_username: Option<String>,
+    pub geoip_source_token: Option<String>,
+    pub geoip_source_token_param: String,
+    pub geoip_source_format: Option<String>,
+    pub geoip_local_dir: Option<PathBuf>,
+    pub geoip_probe_ips: Vec<IpAddr>,
+    pub enable_asn_lookup: bool,
+    pub geoip_asn_db_path: PathBuf,
+    pub asn_privacy: AsnPrivacy,
     // Referrer and User Agent parsing configuration
     pub referrer_db_path: PathBuf,
     pub ua_regexes_path: PathBuf,
+    pub referrer_supplement_path: PathBuf,
+    pub ua_regexes_url: Option<String>,
+    pub referrer_db_url: Option<String>,
+    pub parser_watch_interval: Duration,
+    pub parser_update_interval: Duration,
+    pub channel_rules_path: PathBuf,
+    pub site_config_path: Option<PathBuf>,
+    pub cross_domain_secret: Option<String>,
+    pub cross_domain_token_ttl: Duration,
+    // Revenue configuration
+    pub exchange_rates_path: Option<PathBuf>,
+    pub default_reporting_currency: String,
+    pub order_dedup_window: Duration,
+    // Referrer spam configuration
+    pub referrer_spam_list_path: PathBuf,
+    pub referrer_spam_list_url: Option<String>,
+    pub referrer_spam_update_interval: Duration,
+    pub referrer_spam_action: ReferrerSpamAction,
     pub data_retention_days: i32,
     // Billing configuration
     pub enable_billing: bool,
     // Monitoring configuration
     pub enable_monitoring: bool,
+    // Admin API configuration
+    pub admin_api_token: Option<String>,
 }
 
 impl Config {
@@ -32,6 +109,9 @@ impl Config {
         let root_env_path = PathBuf::from("../.env");
         dotenv::from_path(&root_env_path).ok();
 
+        let geoip_edition = env::var("GEOIP_EDITION")
+            .unwrap_or_else(|_| "GeoLite2-Country".to_string());
+
         Config {
             server_port: env::var("SERVER_PORT")
                 .unwrap_or_else(|_| "3000".to_string())
@@ -55,13 +135,40 @@ impl Config {
             maxmind_license_key: env::var("MAXMIND_LICENSE_KEY").ok(),
             geoip_db_path: env::var("GEOIP_DB_PATH")
                 .map(PathBuf::from)
-                .unwrap_or_else(|_| PathBuf::from("assets/geoip/GeoLite2-Country.mmdb")),
+                .unwrap_or_else(|_| PathBuf::from(format!("assets/geoip/{}.mmdb", geoip_edition))),
+            geoip_edition,
             geoip_update_interval: Duration::from_secs(
                 env::var("GEOIP_UPDATE_INTERVAL")
                     .ok()
                     .and_then(|val| val.parse().ok())
                     .unwrap_or(24 * 60 * 60)
             ),
+            geoip_source: env::var("GEOIP_SOURCE")
+                .unwrap_or_else(|_| "maxmind".to_string()),
+            geoip_source_url: env::var("GEOIP_SOURCE_URL").ok(),
+            geoip_source_checksum_url: env::var("GEOIP_SOURCE_CHECKSUM_URL").ok(),
+            geoip_source_auth: env::var("GEOIP_SOURCE_AUTH")
+                .unwrap_or_else(|_| "none".to_string()),
+            geoip_source_username: env::var("GEOIP_SOURCE_USERNAME").ok(),
+            geoip_source_token: env::var("GEOIP_SOURCE_TOKEN").ok(),
+            geoip_source_token_param: env::var("GEOIP_SOURCE_TOKEN_PARAM")
+                .unwrap_or_else(|_| "token".to_string()),
+            geoip_source_format: env::var("GEOIP_SOURCE_FORMAT").ok(),
+            geoip_local_dir: env::var("GEOIP_LOCAL_DIR").ok().map(PathBuf::from),
+            geoip_probe_ips: env::var("GEOIP_PROBE_IPS")
+                .unwrap_or_else(|_| "8.8.8.8,1.1.1.1".to_string())
+                .split(',')
+                .filter_map(|ip| ip.trim().parse().ok())
+                .collect(),
+            enable_asn_lookup: env::var("ENABLE_ASN_LOOKUP")
+                .map(|val| val.to_lowercase() == "true")
+                .unwrap_or(false),
+            geoip_asn_db_path: env::var("GEOIP_ASN_DB_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("assets/geoip/GeoLite2-ASN.mmdb")),
+            asn_privacy: env::var("ASN_PRIVACY")
+                .map(|val| AsnPrivacy::from_env(&val))
+                .unwrap_or_default(),
             // Referrer and User Agent parsing configuration
             referrer_db_path: env::var("REFERRER_DB_PATH")
                 .map(PathBuf::from)
@@ -69,6 +176,68 @@ impl Config {
             ua_regexes_path: env::var("UA_REGEXES_PATH")
                 .map(PathBuf::from)
                 .unwrap_or_else(|_| PathBuf::from("assets/user_agent_headers/regexes.yaml")),
+            referrer_supplement_path: env::var("REFERRER_SUPPLEMENT_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("assets/referrer_supplement/referers-supplement.json")),
+            ua_regexes_url: env::var("UA_REGEXES_URL").ok().filter(|url| !url.is_empty()),
+            referrer_db_url: env::var("REFERRER_DB_URL").ok().filter(|url| !url.is_empty()),
+            parser_watch_interval: Duration::from_secs(
+                env::var("PARSER_WATCH_INTERVAL")
+                    .ok()
+                    .and_then(|val| val.parse().ok())
+                    .unwrap_or(60)
+            ),
+            parser_update_interval: Duration::from_secs(
+                env::var("PARSER_UPDATE_INTERVAL")
+                    .ok()
+                    .and_then(|val| val.parse().ok())
+                    .unwrap_or(24 * 60 * 60)
+            ),
+            // Channel grouping and per-site configuration
+            channel_rules_path: env::var("CHANNEL_RULES_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("assets/channels/default_channels.json")),
+            site_config_path: env::var("SITE_CONFIG_PATH")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            // Cross-domain tracking, disabled unless a secret is set
+            cross_domain_secret: env::var("CROSS_DOMAIN_SECRET").ok().filter(|secret| !secret.is_empty()),
+            cross_domain_token_ttl: Duration::from_secs(
+                env::var("CROSS_DOMAIN_TOKEN_TTL")
+                    .ok()
+                    .and_then(|val| val.parse().ok())
+                    .unwrap_or(120)
+            ),
+            // Revenue conversion and purchase deduplication
+            exchange_rates_path: env::var("EXCHANGE_RATES_PATH")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            default_reporting_currency: env::var("DEFAULT_REPORTING_CURRENCY")
+                .ok()
+                .and_then(|currency| revenue::normalize_currency(&currency))
+                .unwrap_or_else(|| "USD".to_string()),
+            order_dedup_window: Duration::from_secs(
+                env::var("ORDER_DEDUP_WINDOW")
+                    .ok()
+                    .and_then(|val| val.parse().ok())
+                    .unwrap_or(86400)
+            ),
+            // Referrer spam configuration
+            referrer_spam_list_path: env::var("REFERRER_SPAM_LIST_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("assets/referrer_spam/spammers.txt")),
+            referrer_spam_list_url: env::var("REFERRER_SPAM_LIST_URL").ok(),
+            referrer_spam_update_interval: Duration::from_secs(
+                env::var("REFERRER_SPAM_UPDATE_INTERVAL")
+                    .ok()
+                    .and_then(|val| val.parse().ok())
+                    .unwrap_or(24 * 60 * 60)
+            ),
+            referrer_spam_action: env::var("REFERRER_SPAM_ACTION")
+                .map(|val| ReferrerSpamAction::from_env(&val))
+                .unwrap_or_default(),
             data_retention_days: env::var("DATA_RETENTION_DAYS")
                 .unwrap_or_else(|_| "365".to_string())
                 .parse()
@@ -81,6 +250,8 @@ impl Config {
             enable_monitoring: env::var("ENABLE_MONITORING")
                 .map(|val| val.to_lowercase() == "true")
                 .unwrap_or(false),
+            // Admin API configuration
+            admin_api_token: env::var("ADMIN_API_TOKEN").ok().filter(|token| !token.is_empty()),
         }
     }
 } 
\ No newline at end of file
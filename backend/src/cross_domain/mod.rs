@@ -0,0 +1,131 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("token is malformed")]
+    Malformed,
+    #[error("token signature is invalid")]
+    InvalidSignature,
+    #[error("token expired at {0}")]
+    Expired(u64),
+    #[error("token field contains the '.' separator")]
+    InvalidField,
+}
+
+/// Short-lived signed token that carries a visitor and session across domains of one site.
+/// Encoded as `<site_id>.<visitor_id>.<session_id>.<expires_at>.<hex signature>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossDomainToken {
+    pub site_id: String,
+    pub visitor_id: String,
+    pub session_id: String,
+    /// Unix timestamp in seconds
+    pub expires_at: u64,
+}
+
+impl CrossDomainToken {
+    fn payload(&self) -> String {
+        format!("{}.{}.{}.{}", self.site_id, self.visitor_id, self.session_id, self.expires_at)
+    }
+
+    /// Fails if a field contains the `.` separator, which would make the token ambiguous
+    pub fn sign(&self, secret: &str) -> Result<String, TokenError> {
+        if [&self.site_id, &self.visitor_id, &self.session_id].iter().any(|field| field.contains('.')) {
+            return Err(TokenError::InvalidField);
+        }
+
+        let payload = self.payload();
+        let mut mac = new_mac(secret);
+        mac.update(payload.as_bytes());
+        let signature: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        Ok(format!("{}.{}", payload, signature))
+    }
+
+    /// Parse a token and check its signature and expiry
+    pub fn verify(token: &str, secret: &str, now: u64) -> Result<Self, TokenError> {
+        let (payload, signature) = token.rsplit_once('.').ok_or(TokenError::Malformed)?;
+        let signature = decode_hex(signature).ok_or(TokenError::Malformed)?;
+
+        let mut mac = new_mac(secret);
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| TokenError::InvalidSignature)?;
+
+        let parts: Vec<&str> = payload.split('.').collect();
+        let [site_id, visitor_id, session_id, expires_at] = parts.as_slice() else {
+            return Err(TokenError::Malformed);
+        };
+        let expires_at: u64 = expires_at.parse().map_err(|_| TokenError::Malformed)?;
+        if expires_at < now {
+            return Err(TokenError::Expired(expires_at));
+        }
+
+        Ok(Self {
+            site_id: site_id.to_string(),
+            visitor_id: visitor_id.to_string(),
+            session_id: session_id.to_string(),
+            expires_at,
+        })
+    }
+}
+
+fn new_mac(secret: &str) -> HmacSha256 {
+    HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length")
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    fn token() -> CrossDomainToken {
+        CrossDomainToken {
+            site_id: "site-1".to_string(),
+            visitor_id: "a1b2c3".to_string(),
+            session_id: "V1StGXR8_Z5jdHi6B-myT".to_string(),
+            expires_at: 1_000,
+        }
+    }
+
+    #[test]
+    fn verifies_signed_token() {
+        let signed = token().sign(SECRET).unwrap();
+        assert_eq!(CrossDomainToken::verify(&signed, SECRET, 999).unwrap(), token());
+        assert_eq!(CrossDomainToken::verify(&signed, SECRET, 1_000).unwrap(), token());
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let signed = token().sign(SECRET).unwrap();
+        assert!(matches!(CrossDomainToken::verify(&signed, SECRET, 1_001), Err(TokenError::Expired(1_000))));
+    }
+
+    #[test]
+    fn rejects_tampered_token() {
+        let signed = token().sign(SECRET).unwrap();
+        let tampered = signed.replacen("a1b2c3", "d4e5f6", 1);
+        assert!(matches!(CrossDomainToken::verify(&tampered, SECRET, 0), Err(TokenError::InvalidSignature)));
+        assert!(matches!(CrossDomainToken::verify(&signed, "other-secret", 0), Err(TokenError::InvalidSignature)));
+        assert!(matches!(CrossDomainToken::verify(&signed[..signed.len() - 1], SECRET, 0), Err(TokenError::Malformed)));
+        assert!(matches!(CrossDomainToken::verify("no-signature", SECRET, 0), Err(TokenError::Malformed)));
+    }
+
+    #[test]
+    fn refuses_to_sign_fields_containing_separator() {
+        let token = CrossDomainToken { site_id: "example.com".to_string(), ..token() };
+        assert!(matches!(token.sign(SECRET), Err(TokenError::InvalidField)));
+    }
+}
@@ -1,5 +1,7 @@
 use anyhow::Result;
-use clickhouse::{error::Error as ClickHouseError, Client};
+use clickhouse::{error::Error as ClickHouseError, Client, Row};
+use serde::Serialize;
+use std::fmt::Debug;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -10,11 +12,14 @@ use crate::config::Config;
 use crate::processing::ProcessedEvent;
 
 mod models;
-pub use models::EventRow;
+pub use models::{EventRow, SchemaViolationRow};
 
 const NUM_INSERT_WORKERS: usize = 1;
 const EVENT_CHANNEL_CAPACITY: usize = 100_000;
 const WORKER_CHANNEL_CAPACITY: usize = 10_000;
+const VIOLATION_CHANNEL_CAPACITY: usize = 10_000;
+/// Tables whose TTL follows DATA_RETENTION_DAYS, all keyed on a `timestamp` column
+const RETENTION_TABLES: &[&str] = &["events", "event_schema_violations", "orders"];
 const INSERTER_TIMEOUT_SECS: u64 = 5;
 const INSERTER_PERIOD_SECS: u64 = 10;
 const INSERTER_MAX_ROWS: u64 = 100_000;
@@ -23,6 +28,7 @@ const INSERTER_MAX_BYTES: u64 = 50 * 1024 * 1024;
 pub struct Database {
     client: Client,
     event_tx: mpsc::Sender<ProcessedEvent>,
+    violation_tx: mpsc::Sender<SchemaViolationRow>,
     config: Arc<Config>,
 }
 
@@ -34,8 +40,9 @@ impl Database {
         let (event_tx, event_rx) = Self::create_channels();
         let worker_senders = Self::spawn_inserter_workers(client.clone());
         Self::spawn_dispatcher(event_rx, worker_senders);
+        let violation_tx = Self::spawn_violation_worker(client.clone());
 
-        Ok(Self { client, event_tx, config })
+        Ok(Self { client, event_tx, violation_tx, config })
     }
 
     async fn create_client(config: Arc<Config>) -> Result<Client> {
@@ -49,7 +56,7 @@ impl Database {
         mpsc::channel(EVENT_CHANNEL_CAPACITY)
     }
 
-    fn spawn_inserter_workers(client: Client) -> Vec<mpsc::Sender<ProcessedEvent>> {
+    fn spawn_inserter_workers(client: Client) -> Vec<mpsc::Sender<EventRow>> {
         let mut worker_senders = Vec::with_capacity(NUM_INSERT_WORKERS);
 
         for i in 0..NUM_INSERT_WORKERS {
@@ -57,22 +64,48 @@ impl Database {
             worker_senders.push(worker_tx);
             let client_clone = client.clone();
             tokio::spawn(async move {
-                if let Err(e) = run_inserter_worker(i, client_clone, worker_rx).await {
-                    eprintln!("Worker {}: Error - {}", i, e);
+                let worker_name = format!("Worker {}", i);
+                if let Err(e) = run_inserter_worker(&worker_name, client_clone, "analytics.events", worker_rx).await {
+                    eprintln!("{}: Error - {}", worker_name, e);
                 }
             });
         }
         worker_senders
     }
 
+    /// Schema violations are batched by their own worker, so they don't create a part per event
+    fn spawn_violation_worker(client: Client) -> mpsc::Sender<SchemaViolationRow> {
+        let (violation_tx, violation_rx) = mpsc::channel(VIOLATION_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            let worker_name = "Schema violation worker";
+            if let Err(e) = run_inserter_worker(worker_name, client, "analytics.event_schema_violations", violation_rx).await {
+                eprintln!("{}: Error - {}", worker_name, e);
+            }
+        });
+        violation_tx
+    }
+
     fn spawn_dispatcher(
         mut event_rx: mpsc::Receiver<ProcessedEvent>,
-        worker_senders: Vec<mpsc::Sender<ProcessedEvent>>,
+        worker_senders: Vec<mpsc::Sender<EventRow>>,
     ) {
         tokio::spawn(async move {
             let mut worker_index = 0;
             while let Some(event) = event_rx.recv().await {
-                if let Err(e) = worker_senders[worker_index].send(event).await {
+                let row = EventRow::from_processed(event);
+
+                tracing::debug!(
+                    worker_id = worker_index,
+                    site_id = %row.site_id,
+                    visitor_id = %row.visitor_id,
+                    session_id = %row.session_id,
+                    url = %row.url,
+                    timestamp = %row.timestamp,
+                    device_type = %row.device_type,
+                    browser = %row.browser,
+                    os = %row.os,
+                    "Prepared row for ClickHouse insertion");
+                if let Err(e) = worker_senders[worker_index].send(row).await {
                     eprintln!(
                         "Dispatcher failed to send event to worker {}: {}",
                         worker_index, e
@@ -141,33 +174,38 @@ impl Database {
     }
 
     async fn apply_data_retention_policy(client: &Client, data_retention_days: i32) -> Result<()> {
-        let alter_query = format!(
-            "ALTER TABLE analytics.events MODIFY TTL timestamp + INTERVAL {} DAY",
-            data_retention_days
-        );
-        client.query(&alter_query).execute().await.map_err(|e| 
-            anyhow::anyhow!("Failed to apply data retention policy for analytics.events table: {}.", e)
-        )?;
+        for table in RETENTION_TABLES {
+            let alter_query = format!(
+                "ALTER TABLE analytics.{} MODIFY TTL timestamp + INTERVAL {} DAY",
+                table, data_retention_days
+            );
+            client.query(&alter_query).execute().await.map_err(|e| 
+                anyhow::anyhow!("Failed to apply data retention policy for analytics.{} table: {}.", table, e)
+            )?;
+        }
         Ok(())
     }
 
     async fn remove_data_retention_policy(client: &Client) -> Result<()> {
-        let create_table_query: String = client
-            .query("SELECT create_table_query FROM system.tables WHERE database = 'analytics' AND name = 'events'")
-            .fetch_one()
-            .await?;
-
-        if create_table_query.contains("TTL ") {
-            println!("[INFO] TTL policy exists, removing it.");
-            let alter_query = "ALTER TABLE analytics.events REMOVE TTL";
-            client
-                .query(alter_query)
-                .execute()
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to remove data retention policy: {}", e))?;
-            println!("[INFO] TTL policy removed successfully.");
-        } else {
-            println!("[INFO] No TTL policy found on events table, nothing to remove.");
+        for table in RETENTION_TABLES {
+            let create_table_query: String = client
+                .query("SELECT create_table_query FROM system.tables WHERE database = 'analytics' AND name = ?")
+                .bind(*table)
+                .fetch_one()
+                .await?;
+
+            if create_table_query.contains("TTL ") {
+                println!("[INFO] TTL policy exists on {} table, removing it.", table);
+                let alter_query = format!("ALTER TABLE analytics.{} REMOVE TTL", table);
+                client
+                    .query(&alter_query)
+                    .execute()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to remove data retention policy for analytics.{} table: {}", table, e))?;
+                println!("[INFO] TTL policy removed successfully from {} table.", table);
+            } else {
+                println!("[INFO] No TTL policy found on {} table, nothing to remove.", table);
+            }
         }
 
         Ok(())
@@ -211,6 +249,15 @@ impl Database {
     }
 
     pub async fn insert_event(&self, event: ProcessedEvent) -> Result<()> {
+        for row in SchemaViolationRow::from_processed(&event) {
+            self.violation_tx.send(row).await?;
+        }
+
+        // Events rejected by a strict event schema are only recorded as violations
+        if event.rejected_by_schema {
+            return Ok(());
+        }
+
         self.event_tx.send(event).await?;
         Ok(())
     }
@@ -223,18 +270,20 @@ impl Database {
     }
 }
 
-async fn run_inserter_worker(
-    worker_id: usize,
+/// Batch rows received on the channel into the given table
+async fn run_inserter_worker<T: Row + Serialize + Debug>(
+    worker_name: &str,
     client: Client,
-    mut rx: Receiver<ProcessedEvent>,
+    table: &str,
+    mut rx: Receiver<T>,
 ) -> Result<(), ClickHouseError> {
     println!(
-        "Worker {}: Starting (Inserter Sparse Stream Mode).",
-        worker_id
+        "{}: Starting (Inserter Sparse Stream Mode).",
+        worker_name
     );
 
     let mut inserter = client
-        .inserter("analytics.events")?
+        .inserter::<T>(table)?
         .with_timeouts(
             Some(Duration::from_secs(INSERTER_TIMEOUT_SECS)),
             None,
@@ -243,11 +292,11 @@ async fn run_inserter_worker(
         .with_max_rows(INSERTER_MAX_ROWS)
         .with_max_bytes(INSERTER_MAX_BYTES);
 
-    println!("Worker {}: Inserter configured.", worker_id);
+    println!("{}: Inserter configured.", worker_name);
 
     loop {
-        let event = match rx.try_recv() {
-            Ok(received_event) => received_event,
+        let row = match rx.try_recv() {
+            Ok(received_row) => received_row,
             Err(TryRecvError::Empty) => {
                 // Channel empty, wait for the next event or until the inserter period ends.
                 let time_left = inserter
@@ -255,11 +304,11 @@ async fn run_inserter_worker(
                     .unwrap_or_else(|| Duration::from_secs(INSERTER_PERIOD_SECS));
 
                 match timeout(time_left, rx.recv()).await {
-                    Ok(Some(received_event)) => received_event,
+                    Ok(Some(received_row)) => received_row,
                     Ok(None) => {
                         println!(
-                            "Worker {}: Channel closed during timeout wait. Committing final batch.",
-                            worker_id
+                            "{}: Channel closed during timeout wait. Committing final batch.",
+                            worker_name
                         );
                         inserter.commit().await?;
                         break;
@@ -272,30 +321,17 @@ async fn run_inserter_worker(
             }
             Err(TryRecvError::Disconnected) => {
                 println!(
-                    "Worker {}: Channel disconnected. Committing final batch.",
-                    worker_id
+                    "{}: Channel disconnected. Committing final batch.",
+                    worker_name
                 );
                 break;
             }
         };
 
-        let row = EventRow::from_processed(event);
-
-        tracing::debug!(
-            worker_id = worker_id, 
-            site_id = %row.site_id, 
-            visitor_id = %row.visitor_id,
-            session_id = %row.session_id,
-            url = %row.url, 
-            timestamp = %row.timestamp, 
-            device_type = %row.device_type,
-            browser = %row.browser, 
-            os = %row.os, 
-            "Prepared row for ClickHouse insertion");
         if let Err(e) = inserter.write(&row) {
             eprintln!(
-                "Worker {}: Failed to write row to inserter buffer: {}. Row: {:?}",
-                worker_id, e, row
+                "{}: Failed to write row to inserter buffer: {}. Row: {:?}",
+                worker_name, e, row
             );
             // TODO: Implement retry logic or dead-letter queue for inserter write failures.
             continue;
@@ -303,13 +339,67 @@ async fn run_inserter_worker(
     }
 
     println!(
-        "Worker {}: Exiting loop. Finalizing inserter.",
-        worker_id
+        "{}: Exiting loop. Finalizing inserter.",
+        worker_name
     );
     let stats = inserter.end().await?;
     println!(
-        "Worker {}: Shutdown complete. Final stats: {:?}",
-        worker_id, stats
+        "{}: Shutdown complete. Final stats: {:?}",
+        worker_name, stats
     );
     Ok(())
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::tests::processed_event;
+    use crate::properties::CustomEventProperties;
+    use crate::schema::{EventSchema, SchemaMode};
+
+    fn test_database() -> (Database, Receiver<ProcessedEvent>, Receiver<SchemaViolationRow>) {
+        let (event_tx, event_rx) = mpsc::channel(8);
+        let (violation_tx, violation_rx) = mpsc::channel(8);
+        let database = Database {
+            client: Client::default(),
+            event_tx,
+            violation_tx,
+            config: Arc::new(Config::new()),
+        };
+        (database, event_rx, violation_rx)
+    }
+
+    fn event_with_violations(schema: &EventSchema) -> ProcessedEvent {
+        let mut event = processed_event("purchase");
+        event.schema_violations = schema.validate("purchase", &CustomEventProperties::default());
+        event.rejected_by_schema = schema.mode == SchemaMode::Strict;
+        event
+    }
+
+    #[tokio::test]
+    async fn strict_rejection_records_violations_without_the_event() {
+        let (database, mut event_rx, mut violation_rx) = test_database();
+        let schema: EventSchema = serde_json::from_str(r#"{"mode": "strict"}"#).unwrap();
+
+        database.insert_event(event_with_violations(&schema)).await.unwrap();
+
+        let row = violation_rx.try_recv().unwrap();
+        assert_eq!((row.event_name.as_str(), row.violation.as_str(), row.action.as_str()), ("purchase", "unknown_event", "rejected"));
+        assert!(violation_rx.try_recv().is_err());
+        assert!(event_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn lenient_violations_are_recorded_with_the_event() {
+        let (database, mut event_rx, mut violation_rx) = test_database();
+        let schema = EventSchema::default();
+
+        database.insert_event(event_with_violations(&schema)).await.unwrap();
+
+        assert_eq!(violation_rx.try_recv().unwrap().action, "tagged");
+        assert_eq!(event_rx.try_recv().unwrap().schema_violations.len(), 1);
+
+        database.insert_event(processed_event("purchase")).await.unwrap();
+        assert!(violation_rx.try_recv().is_err());
+        assert!(event_rx.try_recv().is_ok());
+    }
+}
@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use strum_macros::EnumString;
 use crate::processing::ProcessedEvent;
+use crate::revenue::to_decimal;
 
 // Ensure field order exactly matches ClickHouse table schema
 #[derive(clickhouse::Row, Serialize, Debug, Deserialize)]
@@ -33,6 +34,50 @@ pub struct EventRow {
     pub event_type: EventType,
     pub custom_event_name: String,
     pub custom_event_json: String,
+    pub subdivision_code: Option<String>,
+    pub city: Option<String>,
+    pub timezone: Option<String>,
+    pub asn: Option<u32>,
+    pub asn_organization: Option<String>,
+    pub device_brand: String,
+    pub device_model: String,
+    pub browser_version: String,
+    pub os_version: String,
+    pub browser_engine: String,
+    pub channel: String,
+    pub click_id_platform: String,
+    pub utm_id: String,
+    pub utm_source_platform: String,
+    pub utm_creative_format: String,
+    pub utm_marketing_tactic: String,
+    pub session_referrer_source: String,
+    pub session_referrer_source_name: String,
+    pub session_referrer_url: String,
+    pub session_utm_source: String,
+    pub session_utm_medium: String,
+    pub session_utm_campaign: String,
+    pub session_utm_term: String,
+    pub session_utm_content: String,
+    pub url_query: String,
+    pub title: String,
+    pub custom_event_properties: Vec<(String, String)>,
+    pub custom_event_numeric_properties: Vec<(String, f64)>,
+    pub schema_violations: Vec<String>,
+    /// Decimal(18, 4) amounts are written as integers scaled by 10^4
+    pub revenue: i64,
+    pub revenue_currency: String,
+    pub revenue_reporting: Option<i64>,
+    pub reporting_currency: String,
+    pub order_id: String,
+    #[serde(rename = "revenue_items.id")]
+    pub revenue_item_ids: Vec<String>,
+    #[serde(rename = "revenue_items.name")]
+    pub revenue_item_names: Vec<String>,
+    #[serde(rename = "revenue_items.quantity")]
+    pub revenue_item_quantities: Vec<u32>,
+    #[serde(rename = "revenue_items.price")]
+    pub revenue_item_prices: Vec<i64>,
+    pub in_app_source: String,
 }
 
 #[derive(Debug, EnumString, Serialize_repr, Deserialize_repr)]
@@ -46,6 +91,8 @@ pub enum EventType {
 impl EventRow {
     pub fn from_processed(event: ProcessedEvent) -> Self {
         let timestamp = event.timestamp;
+        let revenue = event.revenue.unwrap_or_default();
+        let items = &revenue.items;
 
         Self {
             site_id: event.site_id,
@@ -72,6 +119,81 @@ impl EventRow {
             event_type: event.event_type.parse().unwrap(),
             custom_event_name: event.custom_event_name,
             custom_event_json: event.custom_event_json,
+            subdivision_code: event.subdivision_code,
+            city: event.city,
+            timezone: event.timezone,
+            asn: event.asn,
+            asn_organization: event.asn_organization,
+            device_brand: event.device_brand.unwrap_or_default(),
+            device_model: event.device_model.unwrap_or_default(),
+            browser_version: event.browser_version_full.unwrap_or_default(),
+            os_version: event.os_version.unwrap_or_default(),
+            browser_engine: event.browser_engine.unwrap_or_default(),
+            channel: event.channel,
+            click_id_platform: event.campaign_info.click_id_platform.unwrap_or_default(),
+            utm_id: event.campaign_info.utm_id.unwrap_or_default(),
+            utm_source_platform: event.campaign_info.utm_source_platform.unwrap_or_default(),
+            utm_creative_format: event.campaign_info.utm_creative_format.unwrap_or_default(),
+            utm_marketing_tactic: event.campaign_info.utm_marketing_tactic.unwrap_or_default(),
+            // Sessions without an external first touch are direct
+            session_referrer_source: event.session_attribution.referrer_source.unwrap_or_else(|| "direct".to_string()),
+            session_referrer_source_name: event.session_attribution.referrer_source_name.unwrap_or_default(),
+            session_referrer_url: event.session_attribution.referrer_url.unwrap_or_default(),
+            session_utm_source: event.session_attribution.utm_source.unwrap_or_default(),
+            session_utm_medium: event.session_attribution.utm_medium.unwrap_or_default(),
+            session_utm_campaign: event.session_attribution.utm_campaign.unwrap_or_default(),
+            session_utm_term: event.session_attribution.utm_term.unwrap_or_default(),
+            session_utm_content: event.session_attribution.utm_content.unwrap_or_default(),
+            url_query: event.url_query,
+            title: event.title.unwrap_or_default(),
+            custom_event_properties: event.custom_event_properties.strings,
+            custom_event_numeric_properties: event.custom_event_properties.numbers,
+            schema_violations: event.schema_violations.iter().map(|violation| violation.tag()).collect(),
+            revenue: to_decimal(revenue.amount),
+            revenue_currency: revenue.currency.clone(),
+            revenue_reporting: revenue.reporting_amount.map(to_decimal),
+            reporting_currency: revenue.reporting_currency.clone(),
+            order_id: revenue.order_id.clone().unwrap_or_default(),
+            revenue_item_ids: items.iter().map(|item| item.id.clone().unwrap_or_default()).collect(),
+            revenue_item_names: items.iter().map(|item| item.name.clone().unwrap_or_default()).collect(),
+            revenue_item_quantities: items.iter().map(|item| item.quantity.unwrap_or(1.0) as u32).collect(),
+            revenue_item_prices: items.iter().map(|item| to_decimal(item.price.unwrap_or_default())).collect(),
+            in_app_source: event.in_app_source.unwrap_or_default(),
         }
     }
-}
\ No newline at end of file
+}
+
+// Ensure field order exactly matches the analytics.event_schema_violations table schema
+#[derive(clickhouse::Row, Serialize, Debug, Deserialize)]
+pub struct SchemaViolationRow {
+    pub site_id: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::date")]
+    pub date: NaiveDate,
+    pub event_name: String,
+    pub violation: String,
+    pub property: String,
+    pub detail: String,
+    pub action: String,
+}
+
+impl SchemaViolationRow {
+    pub fn from_processed(event: &ProcessedEvent) -> Vec<Self> {
+        let action = if event.rejected_by_schema { "rejected" } else { "tagged" };
+
+        event.schema_violations
+            .iter()
+            .map(|violation| Self {
+                site_id: event.site_id.clone(),
+                timestamp: event.timestamp,
+                date: event.timestamp.date_naive(),
+                event_name: event.custom_event_name.clone(),
+                violation: violation.kind.as_str().to_string(),
+                property: violation.property.clone(),
+                detail: violation.detail.clone(),
+                action: action.to_string(),
+            })
+            .collect()
+    }
+}
@@ -1,75 +1,122 @@
-use maxminddb::{geoip2, Reader};
+use maxminddb::{geoip2, MaxMindDbError, Reader};
 use std::net::IpAddr;
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use std::sync::atomic::{AtomicU64, Ordering};
 use tracing::{info, warn, error, debug};
-use crate::config::Config;
-use crate::geoip_updater::GeoIpWatchRx;
-use anyhow::Result;
+use crate::analytics::{IpClass, classify_ip};
+use crate::config::{AsnPrivacy, Config};
+use crate::geoip_updater::{GeoIpDatabase, GeoIpWatchRx};
+use anyhow::{Result, Context};
+use serde::Serialize;
 use moka::sync::Cache;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const CACHE_TTI: Duration = Duration::from_secs(1200);
 const CACHE_SIZE: u64 = 100000; // Cache up to 100k IP addresses
+const ASN_ORGANIZATION_MAX_LENGTH: usize = 64;
 const READER_UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(1200); // Check for reader updates every 20 minutes
 
+/// Geolocation result for a single IP address.
+/// Only `country_code` is populated when a Country database is loaded.
+#[derive(Debug, Clone, Default)]
+pub struct GeoLocation {
+    pub country_code: Option<String>,
+    /// ISO 3166-2 subdivision code (e.g. "US-CA")
+    pub subdivision_code: Option<String>,
+    /// English city name
+    pub city: Option<String>,
+    /// IANA time zone (e.g. "America/Los_Angeles")
+    pub timezone: Option<String>,
+}
+
+impl GeoLocation {
+    fn country_only(country_code: &str) -> Self {
+        Self {
+            country_code: Some(country_code.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Autonomous system information for a single IP address
+#[derive(Debug, Clone, Default)]
+pub struct AsnInfo {
+    pub number: Option<u32>,
+    /// Organization owning the AS (e.g. "Comcast Cable Communications, LLC")
+    pub organization: Option<String>,
+}
+
+impl AsnInfo {
+    /// Reduce the stored ASN detail according to the configured privacy mode
+    pub fn apply_privacy(self, privacy: AsnPrivacy) -> Self {
+        let organization = self.organization
+            .map(|org| org.chars().take(ASN_ORGANIZATION_MAX_LENGTH).collect());
+
+        match privacy {
+            AsnPrivacy::Full => Self { number: self.number, organization },
+            AsnPrivacy::Organization => Self { number: None, organization },
+            AsnPrivacy::Off => Self::default(),
+        }
+    }
+}
+
+/// A single mmdb database that is swapped out when its `GeoIpUpdater` publishes a new version
 #[derive(Clone)]
-pub struct GeoIpService {
-    geoip_watch_rx: Arc<Mutex<GeoIpWatchRx>>,
+struct DatabaseHandle {
+    database: GeoIpDatabase,
+    watch_rx: Arc<Mutex<GeoIpWatchRx>>,
     current_reader: Arc<RwLock<Option<Arc<Reader<Vec<u8>>>>>>,
-    ip_cache: Cache<String, Option<String>>,
     last_reader_check: Arc<AtomicU64>,
 }
 
-impl GeoIpService {
-    pub fn new(config: Arc<Config>, geoip_watch_rx: GeoIpWatchRx) -> Result<Self> {
+impl DatabaseHandle {
+    fn new(database: GeoIpDatabase, watch_rx: GeoIpWatchRx) -> Self {
         let mut initial_reader = None;
-        if config.enable_geolocation {
-            let db_path = &config.geoip_db_path;
+        if database.enabled {
+            let db_path = &database.db_path;
             if db_path.exists() {
-                info!("Loading initial GeoIP database from: {:?}", db_path);
+                info!("Loading initial {} database from: {:?}", database.edition, db_path);
                 match Reader::open_readfile(db_path) {
                     Ok(reader) => {
-                        info!("Initial GeoIP database loaded successfully.");
+                        info!("Initial {} database loaded successfully.", database.edition);
                         initial_reader = Some(Arc::new(reader));
                     }
                     Err(e) => {
-                        error!("Failed to load initial GeoIP database from {:?}: {}. Geolocation may be delayed until first update.", db_path, e);
+                        error!("Failed to load initial {} database from {:?}: {}. Lookups may be delayed until first update.", database.edition, db_path, e);
                     }
                 }
             } else {
-                warn!("Initial GeoIP database file not found at {:?}. Geolocation will be disabled until first update.", db_path);
+                warn!("Initial {} database file not found at {:?}. Lookups will be disabled until first update.", database.edition, db_path);
             }
         } else {
-            info!("Geolocation is disabled via config.");
+            info!("{} lookups are disabled via config.", database.edition);
         }
 
-        let rx_mutex = Arc::new(Mutex::new(geoip_watch_rx));
+        let rx_mutex = Arc::new(Mutex::new(watch_rx));
 
         let current_reader_state = rx_mutex.lock().unwrap().borrow().clone();
 
         let reader_to_use = current_reader_state.or(initial_reader);
 
-        // LRU cache with session-aligned TTI
-        let cache = Cache::builder()
-            .max_capacity(CACHE_SIZE)
-            .time_to_idle(CACHE_TTI)
-            .build();
-
         let now_secs = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
-        Ok(Self {
-            geoip_watch_rx: rx_mutex,
+        Self {
+            database,
+            watch_rx: rx_mutex,
             current_reader: Arc::new(RwLock::new(reader_to_use)),
-            ip_cache: cache,
             last_reader_check: Arc::new(AtomicU64::new(now_secs)),
-        })
+        }
     }
 
-    fn update_reader_if_changed(&self) {
+    fn reader(&self) -> Option<Arc<Reader<Vec<u8>>>> {
+        self.current_reader.read().unwrap().clone()
+    }
+
+    /// Swaps in the latest reader from the watch channel. Returns true if the reader changed.
+    fn update_reader_if_changed(&self) -> bool {
         let now_secs = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
@@ -78,7 +125,7 @@ impl GeoIpService {
         let last_check_secs = self.last_reader_check.load(Ordering::Relaxed);
         
         if now_secs.saturating_sub(last_check_secs) < READER_UPDATE_CHECK_INTERVAL.as_secs() {
-            return;
+            return false;
         }
 
         // Try to atomically update the timestamp to claim the right to check
@@ -87,18 +134,31 @@ impl GeoIpService {
             .compare_exchange_weak(last_check_secs, now_secs, Ordering::Relaxed, Ordering::Relaxed)
             .is_err()
         {
-            return;
+            return false;
         }
 
         // We successfully claimed the right to check for update
-        let mut rx_guard = match self.geoip_watch_rx.try_lock() {
+        let rx_guard = match self.watch_rx.try_lock() {
             Ok(guard) => guard,
             Err(_) => {
                 self.last_reader_check.store(last_check_secs, Ordering::Relaxed);
-                return;
+                return false;
             }
         };
 
+        Self::apply_pending_update(rx_guard, &self.current_reader)
+    }
+
+    /// Swaps in the latest reader from the watch channel without waiting for the check interval.
+    fn force_update_from_watch(&self) -> bool {
+        let rx_guard = self.watch_rx.lock().unwrap();
+        Self::apply_pending_update(rx_guard, &self.current_reader)
+    }
+
+    fn apply_pending_update(
+        mut rx_guard: MutexGuard<'_, GeoIpWatchRx>,
+        current_reader: &RwLock<Option<Arc<Reader<Vec<u8>>>>>,
+    ) -> bool {
         if rx_guard.has_changed().unwrap_or(false) {
             let latest_reader_option = rx_guard.borrow_and_update().clone();
             debug!("GeoIpService detected database update via watch channel.");
@@ -106,54 +166,274 @@ impl GeoIpService {
             // Drop the rx_guard before acquiring the write lock to avoid holding multiple locks
             drop(rx_guard);
             
-            let mut current_reader_guard = self.current_reader.write().unwrap();
+            let mut current_reader_guard = current_reader.write().unwrap();
             *current_reader_guard = latest_reader_option;
             drop(current_reader_guard);
-            
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Reopens the database file from disk. Returns false if lookups for this database are disabled.
+    fn reload_from_disk(&self) -> Result<bool> {
+        if !self.database.enabled {
+            return Ok(false);
+        }
+
+        let reader = Reader::open_readfile(&self.database.db_path)
+            .with_context(|| format!("Failed to load {} database from {:?}", self.database.edition, self.database.db_path))?;
+        *self.current_reader.write().unwrap() = Some(Arc::new(reader));
+        info!("Reloaded {} database from {:?}", self.database.edition, self.database.db_path);
+        Ok(true)
+    }
+
+    fn status(&self) -> DatabaseStatus {
+        let reader = self.reader();
+        DatabaseStatus {
+            edition: self.database.edition.clone(),
+            enabled: self.database.enabled,
+            db_path: self.database.db_path.display().to_string(),
+            loaded: reader.is_some(),
+            database_type: reader.as_ref().map(|r| r.metadata.database_type.clone()),
+            build_epoch: reader.as_ref().map(|r| r.metadata.build_epoch),
+            ip_version: reader.as_ref().map(|r| r.metadata.ip_version),
+            node_count: reader.as_ref().map(|r| r.metadata.node_count),
+        }
+    }
+}
+
+/// Metadata of a loaded database, reported by the admin status endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseStatus {
+    pub edition: String,
+    pub enabled: bool,
+    pub db_path: String,
+    pub loaded: bool,
+    pub database_type: Option<String>,
+    pub build_epoch: Option<u64>,
+    pub ip_version: Option<u16>,
+    pub node_count: Option<u32>,
+}
+
+/// Live GeoIP state, reported by the admin status endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct GeoIpServiceStatus {
+    pub location_database: DatabaseStatus,
+    pub asn_database: DatabaseStatus,
+    pub location_cache_entries: u64,
+    pub asn_cache_entries: u64,
+}
+
+#[derive(Clone)]
+pub struct GeoIpService {
+    location_db: DatabaseHandle,
+    asn_db: DatabaseHandle,
+    ip_cache: Cache<IpAddr, GeoLocation>,
+    asn_cache: Cache<IpAddr, AsnInfo>,
+}
+
+impl GeoIpService {
+    pub fn new(config: Arc<Config>, geoip_watch_rx: GeoIpWatchRx, asn_watch_rx: GeoIpWatchRx) -> Result<Self> {
+        let location_db = DatabaseHandle::new(GeoIpDatabase::location(&config), geoip_watch_rx);
+        let asn_db = DatabaseHandle::new(GeoIpDatabase::asn(&config), asn_watch_rx);
+
+        // LRU caches with session-aligned TTI
+        let ip_cache = Cache::builder()
+            .max_capacity(CACHE_SIZE)
+            .time_to_idle(CACHE_TTI)
+            .build();
+
+        let asn_cache = Cache::builder()
+            .max_capacity(CACHE_SIZE)
+            .time_to_idle(CACHE_TTI)
+            .build();
+
+        Ok(Self {
+            location_db,
+            asn_db,
+            ip_cache,
+            asn_cache,
+        })
+    }
+
+    /// Applies updates published by the updaters immediately, bypassing the reader check interval
+    pub fn force_refresh(&self) {
+        if self.location_db.force_update_from_watch() {
             self.ip_cache.invalidate_all();
             info!("GeoIP cache cleared due to database update");
         }
+        if self.asn_db.force_update_from_watch() {
+            self.asn_cache.invalidate_all();
+            info!("ASN cache cleared due to database update");
+        }
     }
 
-    pub fn lookup_country_code(&self, ip_address: &str) -> Option<String> {
-        if ip_address == "127.0.0.1" || ip_address == "::1" {
-            return Some("Localhost".to_string());
+    /// Reopens the database files from disk and clears the caches
+    pub fn reload_from_disk(&self) -> Result<()> {
+        if self.location_db.reload_from_disk()? {
+            self.ip_cache.invalidate_all();
+        }
+        if self.asn_db.reload_from_disk()? {
+            self.asn_cache.invalidate_all();
         }
+        Ok(())
+    }
+
+    pub fn status(&self) -> GeoIpServiceStatus {
+        GeoIpServiceStatus {
+            location_database: self.location_db.status(),
+            asn_database: self.asn_db.status(),
+            location_cache_entries: self.ip_cache.entry_count(),
+            asn_cache_entries: self.asn_cache.entry_count(),
+        }
+    }
 
-        if let Some(cached_result) = self.ip_cache.get(ip_address) {
-            debug!("GeoIP cache hit for IP: {}", ip_address);
+    pub fn lookup(&self, ip: IpAddr) -> GeoLocation {
+        if let Some(sentinel) = classify_ip(&ip).sentinel_code() {
+            return GeoLocation::country_only(sentinel);
+        }
+
+        if let Some(cached_result) = self.ip_cache.get(&ip) {
+            debug!("GeoIP cache hit for IP: {}", ip);
             return cached_result;
         }
 
-        debug!("GeoIP cache miss for IP: {}", ip_address);
+        debug!("GeoIP cache miss for IP: {}", ip);
 
-        self.update_reader_if_changed();
+        if self.location_db.update_reader_if_changed() {
+            self.ip_cache.invalidate_all();
+            info!("GeoIP cache cleared due to database update");
+        }
 
-        let reader_arc_option = self.current_reader.read().unwrap().clone();
-        let reader = reader_arc_option?;
+        let Some(reader) = self.location_db.reader() else {
+            return GeoLocation::default();
+        };
 
-        let ip: IpAddr = match ip_address.parse() {
-            Ok(ip) => ip,
+        let result = if reader.metadata.database_type.contains("City") {
+            Self::lookup_city(&reader, ip)
+        } else {
+            Self::lookup_country(&reader, ip)
+        };
+
+        let result = match result {
+            Ok(location) => location.unwrap_or_default(),
             Err(e) => {
-                warn!("Failed to parse IP address '{}': {}", ip_address, e);
-                self.ip_cache.insert(ip_address.to_string(), None);
-                return None;
+                warn!("GeoIP lookup failed for IP {}: {}", ip, e);
+                GeoLocation::default()
             }
         };
 
-        let result = match reader.lookup::<geoip2::Country>(ip) {
+        self.ip_cache.insert(ip, result.clone());
+        
+        result
+    }
+
+    pub fn lookup_asn(&self, ip: IpAddr) -> AsnInfo {
+        if classify_ip(&ip) != IpClass::Public {
+            return AsnInfo::default();
+        }
+
+        if let Some(cached_result) = self.asn_cache.get(&ip) {
+            debug!("ASN cache hit for IP: {}", ip);
+            return cached_result;
+        }
+
+        debug!("ASN cache miss for IP: {}", ip);
+
+        if self.asn_db.update_reader_if_changed() {
+            self.asn_cache.invalidate_all();
+            info!("ASN cache cleared due to database update");
+        }
+
+        let Some(reader) = self.asn_db.reader() else {
+            return AsnInfo::default();
+        };
+
+        let result = match reader.lookup::<geoip2::Asn>(ip) {
             Ok(lookup_result) => lookup_result
-                .and_then(|geoip_data| geoip_data.country)
-                .and_then(|country_data| country_data.iso_code)
-                .map(|s| s.to_string()),
+                .map(|asn_data| AsnInfo {
+                    number: asn_data.autonomous_system_number,
+                    organization: asn_data.autonomous_system_organization.map(|s| s.to_string()),
+                })
+                .unwrap_or_default(),
             Err(e) => {
-                warn!("GeoIP lookup failed for IP {}: {}", ip_address, e);
-                None
+                warn!("ASN lookup failed for IP {}: {}", ip, e);
+                AsnInfo::default()
             }
         };
 
-        self.ip_cache.insert(ip_address.to_string(), result.clone());
-        
+        self.asn_cache.insert(ip, result.clone());
+
         result
     }
+
+    fn lookup_country(reader: &Reader<Vec<u8>>, ip: IpAddr) -> Result<Option<GeoLocation>, MaxMindDbError> {
+        let result = reader.lookup::<geoip2::Country>(ip)?;
+        Ok(result.map(|geoip_data| GeoLocation {
+            country_code: geoip_data.country
+                .and_then(|country_data| country_data.iso_code)
+                .map(|s| s.to_string()),
+            ..Default::default()
+        }))
+    }
+
+    fn lookup_city(reader: &Reader<Vec<u8>>, ip: IpAddr) -> Result<Option<GeoLocation>, MaxMindDbError> {
+        let result = reader.lookup::<geoip2::City>(ip)?;
+        Ok(result.map(|geoip_data| {
+            let country_code = geoip_data.country
+                .and_then(|country_data| country_data.iso_code)
+                .map(|s| s.to_string());
+
+            let subdivision_code = geoip_data.subdivisions
+                .and_then(|subdivisions| subdivisions.into_iter().next())
+                .and_then(|subdivision| subdivision.iso_code)
+                .map(|code| match &country_code {
+                    Some(country) => format!("{}-{}", country, code),
+                    None => code.to_string(),
+                });
+
+            let city = geoip_data.city
+                .and_then(|city_data| city_data.names)
+                .and_then(|names| names.get("en").map(|name| name.to_string()));
+
+            let timezone = geoip_data.location
+                .and_then(|location| location.time_zone)
+                .map(|s| s.to_string());
+
+            GeoLocation {
+                country_code,
+                subdivision_code,
+                city,
+                timezone,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geoip_updater::tests::test_database;
+    use tokio::sync::watch;
+
+    #[test]
+    fn only_public_addresses_are_looked_up() {
+        let reader = Reader::from_source(test_database(1)).unwrap();
+        let (_location_tx, location_rx) = watch::channel(Some(Arc::new(reader)));
+        let (_asn_tx, asn_rx) = watch::channel(None);
+        let service = GeoIpService::new(Arc::new(Config::new()), location_rx, asn_rx).unwrap();
+
+        for (ip, sentinel) in [("127.0.0.1", "Localhost"), ("::ffff:192.168.1.1", "Private"), ("100.64.0.1", "CGNAT"), ("0.0.0.0", "Reserved")] {
+            let ip = crate::analytics::normalize_ip(ip.parse().unwrap());
+            assert_eq!(service.lookup(ip).country_code.as_deref(), Some(sentinel));
+            assert!(!service.ip_cache.contains_key(&ip));
+        }
+
+        // Public addresses go to the database, whose test records carry no country
+        let public: IpAddr = "8.8.8.8".parse().unwrap();
+        assert_eq!(service.lookup(public).country_code, None);
+        assert!(service.ip_cache.contains_key(&public));
+    }
 }
@@ -1,9 +1,12 @@
 use crate::config::Config;
+use crate::metrics::MetricsCollector;
 use maxminddb::Reader;
 use std::fs;
 use std::io::{Cursor, Read};
+use std::net::IpAddr;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use tokio::sync::watch;
 use tokio::time::interval;
@@ -14,45 +17,171 @@ use flate2::read::GzDecoder;
 use tar::Archive;
 use httpdate::parse_http_date;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 
-const GEOIP_DATABASE_URL: &str = "https://download.maxmind.com/geoip/databases/GeoLite2-Country/download?suffix=tar.gz";
+mod source;
+mod verify;
+use source::{ArchiveFormat, GeoIpSource};
+use verify::{IntegrityError, verify_checksum, verify_database};
+
+const GEOIP_ASN_EDITION: &str = "GeoLite2-ASN";
+
+/// Describes a single mmdb database kept up to date by a `GeoIpUpdater`
+#[derive(Debug, Clone)]
+pub struct GeoIpDatabase {
+    /// MaxMind edition ID (e.g. "GeoLite2-City")
+    pub edition: String,
+    pub db_path: PathBuf,
+    pub enabled: bool,
+}
+
+impl GeoIpDatabase {
+    /// The country or city database used for geolocation
+    pub fn location(config: &Config) -> Self {
+        Self {
+            edition: config.geoip_edition.clone(),
+            db_path: config.geoip_db_path.clone(),
+            enabled: config.enable_geolocation,
+        }
+    }
+
+    /// The ASN database used for network organization lookups
+    pub fn asn(config: &Config) -> Self {
+        Self {
+            edition: GEOIP_ASN_EDITION.to_string(),
+            db_path: config.geoip_asn_db_path.clone(),
+            enabled: config.enable_asn_lookup,
+        }
+    }
+}
 
 /// Notifies watchers when the GeoIP database is updated.
 pub type GeoIpWatchRx = watch::Receiver<Option<Arc<Reader<Vec<u8>>>>>;
 /// Sends notifications when the GeoIP database is updated.
 pub type GeoIpWatchTx = watch::Sender<Option<Arc<Reader<Vec<u8>>>>>;
 
+/// Result of a single update attempt, used as the metrics label
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpdateOutcome {
+    Updated,
+    UpToDate,
+    /// The new database failed integrity verification and was not installed
+    Rejected,
+    Failed,
+}
+
+impl UpdateOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UpdateOutcome::Updated => "updated",
+            UpdateOutcome::UpToDate => "up_to_date",
+            UpdateOutcome::Rejected => "rejected",
+            UpdateOutcome::Failed => "failed",
+        }
+    }
+}
+
+/// Result of comparing the source against the local database
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UpdateCheck {
+    UpToDate,
+    /// The source differs from the local database. Carries the source's ETag, which is stored once the
+    /// update is handled so sources without Last-Modified are not downloaded on every check.
+    Needed { etag: Option<String> },
+}
+
+#[derive(Debug, Default)]
+struct LastCheck {
+    checked_at: Option<DateTime<Utc>>,
+    outcome: Option<&'static str>,
+    /// Most recent error, kept after later successful checks
+    error: Option<String>,
+}
+
+/// Updater state reported by the admin status endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdaterStatus {
+    pub edition: String,
+    pub enabled: bool,
+    pub current_build_epoch: Option<u64>,
+    pub last_check: Option<DateTime<Utc>>,
+    pub last_outcome: Option<&'static str>,
+    pub last_error: Option<String>,
+}
+
 pub struct GeoIpUpdater {
-    config: Arc<Config>,
     client: Client,
-    db_path: PathBuf,
+    database: GeoIpDatabase,
+    source: Option<GeoIpSource>,
     update_interval: Duration,
     watch_tx: GeoIpWatchTx,
+    probe_ips: Vec<IpAddr>,
+    /// Build epoch of the database currently on disk, 0 if none is loaded
+    current_build_epoch: AtomicU64,
+    metrics: Option<Arc<MetricsCollector>>,
+    last_check: Mutex<LastCheck>,
+    /// Held for a whole update check, so the background loop and admin-triggered updates never write the
+    /// temporary and backup files at the same time
+    update_lock: tokio::sync::Mutex<()>,
 }
 
 impl GeoIpUpdater {
     /// Creates a new updater and returns it along with a watch receiver.
-    pub fn new(config: Arc<Config>) -> Result<(Self, GeoIpWatchRx)> {
+    /// An invalid source configuration disables auto-updates for the database instead of failing.
+    pub fn new(config: Arc<Config>, database: GeoIpDatabase, metrics: Option<Arc<MetricsCollector>>) -> Result<(Self, GeoIpWatchRx)> {
+        let source = match GeoIpSource::from_config(&config) {
+            Ok(source) => source,
+            Err(e) => {
+                error!("Invalid GeoIP source configuration: {:#}. {} database auto-update disabled.", e, database.edition);
+                None
+            }
+        };
+
+        Self::with_source(database, source, config.geoip_update_interval, config.geoip_probe_ips.clone(), metrics)
+    }
+
+    /// Creates an updater for an explicit source, e.g. a local mirror.
+    fn with_source(
+        database: GeoIpDatabase,
+        source: Option<GeoIpSource>,
+        update_interval: Duration,
+        probe_ips: Vec<IpAddr>,
+        metrics: Option<Arc<MetricsCollector>>,
+    ) -> Result<(Self, GeoIpWatchRx)> {
         let (watch_tx, watch_rx) = watch::channel(None);
 
+        let current_build_epoch = Reader::open_readfile(&database.db_path)
+            .map(|reader| reader.metadata.build_epoch)
+            .unwrap_or(0);
+
+        if let Some(metrics) = &metrics && current_build_epoch > 0 {
+            metrics.set_geoip_build_epoch(&database.edition, current_build_epoch);
+        }
+
         let updater = Self {
             client: Client::builder().user_agent("betterlytics-updater/0.1").build()?,
-            db_path: config.geoip_db_path.clone(),
-            update_interval: config.geoip_update_interval,
-            config,
+            source,
+            database,
+            update_interval,
             watch_tx,
+            probe_ips,
+            current_build_epoch: AtomicU64::new(current_build_epoch),
+            metrics,
+            last_check: Mutex::new(LastCheck::default()),
+            update_lock: tokio::sync::Mutex::new(()),
         };
         Ok((updater, watch_rx))
     }
 
     /// Starts the background update check loop.
     pub async fn run(self: Arc<Self>) {
-        if !self.config.enable_geolocation || self.config.maxmind_account_id.is_none() || self.config.maxmind_license_key.is_none() {
-            info!("GeoIP database auto-update disabled (geolocation disabled or credentials missing).");
+        if !self.is_configured() {
+            info!("{} database auto-update disabled (lookups disabled or no source configured).", self.database.edition);
             return;
         }
 
-        info!("Starting GeoIP database update loop every {:?}", self.update_interval);
+        info!("Starting {} database update loop every {:?}", self.database.edition, self.update_interval);
         let mut interval = interval(self.update_interval);
 
         interval.tick().await; 
@@ -64,120 +193,315 @@ impl GeoIpUpdater {
         }
     }
 
-    /// Checks if an update is needed via HEAD request and then downloads if necessary.
-    async fn check_and_update(&self) {
-        info!("Checking for GeoIP database updates...");
-        match self.is_update_needed().await {
-            Ok(true) => {
-                info!("Remote GeoIP database is newer or local file missing. Downloading...");
+    /// Checks if an update is needed and then downloads if necessary.
+    /// Waits for an update check that is already in progress to finish first.
+    pub async fn check_and_update(&self) -> UpdaterStatus {
+        if !self.is_configured() {
+            return self.status();
+        }
+
+        let _update_guard = self.update_lock.lock().await;
+
+        info!("Checking for {} database updates...", self.database.edition);
+        let mut last_error = None;
+        let outcome = match self.is_update_needed().await {
+            Ok(UpdateCheck::Needed { etag }) => {
+                info!("Source {} database is newer or local file missing. Downloading...", self.database.edition);
+
+                let result = self.download_and_replace().await;
+                if result.is_ok() {
+                    self.store_etag(etag.as_deref());
+                }
 
-                match self.download_and_replace().await {
-                    Ok(new_reader) => {
-                        info!("GeoIP database updated successfully.");
+                match result {
+                    Ok(Some(new_reader)) => {
+                        info!("{} database updated successfully.", self.database.edition);
+                        let build_epoch = new_reader.metadata.build_epoch;
+                        self.current_build_epoch.store(build_epoch, Ordering::Relaxed);
+                        if let Some(metrics) = &self.metrics {
+                            metrics.set_geoip_build_epoch(&self.database.edition, build_epoch);
+                        }
                         if self.watch_tx.send(Some(Arc::new(new_reader))).is_err() {
                             warn!("GeoIP watch channel closed, receiver likely dropped.");
                         }
+                        UpdateOutcome::Updated
+                    }
+                    Ok(None) => {
+                        debug!("Fetched {} database is identical to the local file.", self.database.edition);
+                        UpdateOutcome::UpToDate
+                    }
+                    Err(e) if e.downcast_ref::<IntegrityError>().is_some() => {
+                        error!("Rejected {} database update: {}", self.database.edition, e);
+                        last_error = Some(format!("{:#}", e));
+                        UpdateOutcome::Rejected
                     }
                     Err(e) => {
-                        error!("Failed to download and replace GeoIP database: {}", e);
+                        error!("Failed to download and replace {} database: {}", self.database.edition, e);
+                        last_error = Some(format!("{:#}", e));
+                        UpdateOutcome::Failed
                     }
                 }
             }
-            Ok(false) => {
-                debug!("Local GeoIP database is up-to-date.");
+            Ok(UpdateCheck::UpToDate) => {
+                debug!("Local {} database is up-to-date.", self.database.edition);
+                UpdateOutcome::UpToDate
             }
             Err(e) => {
-                error!("Failed to check for GeoIP database update: {}", e);
+                error!("Failed to check for {} database update: {}", self.database.edition, e);
+                last_error = Some(format!("{:#}", e));
+                UpdateOutcome::Failed
             }
+        };
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_geoip_update(&self.database.edition, outcome.as_str());
+        }
+
+        let mut last_check = self.last_check.lock().unwrap();
+        last_check.checked_at = Some(Utc::now());
+        last_check.outcome = Some(outcome.as_str());
+        if last_error.is_some() {
+            last_check.error = last_error;
         }
+        drop(last_check);
+
+        self.status()
     }
 
-    /// Checks remote Last-Modified header against local file modified time.
-    async fn is_update_needed(&self) -> Result<bool> {
-        let account_id = self.config.maxmind_account_id.as_ref().unwrap();
-        let license_key = self.config.maxmind_license_key.as_ref().unwrap();
+    /// Whether lookups for the database are enabled and a source to update it from is configured
+    pub fn is_configured(&self) -> bool {
+        self.database.enabled && self.source.is_some()
+    }
 
-        debug!("Sending HEAD request to {}", GEOIP_DATABASE_URL);
-        let response = self.client
-            .head(GEOIP_DATABASE_URL)
-            .basic_auth(account_id, Some(license_key))
-            .send()
-            .await?;
+    /// Reports the result of the most recent update check
+    pub fn status(&self) -> UpdaterStatus {
+        let last_check = self.last_check.lock().unwrap();
+        let last_outcome = if !self.database.enabled {
+            Some("disabled")
+        } else if self.source.is_none() {
+            Some("not_configured")
+        } else {
+            last_check.outcome
+        };
+        UpdaterStatus {
+            edition: self.database.edition.clone(),
+            enabled: self.is_configured(),
+            current_build_epoch: match self.current_build_epoch.load(Ordering::Relaxed) {
+                0 => None,
+                epoch => Some(epoch),
+            },
+            last_check: last_check.checked_at,
+            last_outcome,
+            last_error: last_check.error.clone(),
+        }
+    }
 
-        if !response.status().is_success() {
-            anyhow::bail!("HEAD request failed: {}", response.status());
-        }
-
-        let remote_last_modified_str = response
-            .headers()
-            .get(header::LAST_MODIFIED)
-            .context("No Last-Modified header found in HEAD response")?
-            .to_str()
-            .context("Last-Modified header is not valid UTF-8")?;
-            
-        let remote_time = parse_http_date(remote_last_modified_str)
-            .context("Failed to parse Last-Modified header date")?;
-        debug!("Remote database Last-Modified: {:?}", remote_time);
-
-        match fs::metadata(&self.db_path) {
-            Ok(metadata) => {
-                let local_time = metadata.modified().context("Failed to get local file modification time")?;
-                debug!("Local database modified: {:?}", local_time);
-                Ok(remote_time > local_time)
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                info!("Local database file not found at {:?}. Update needed.", self.db_path);
-                Ok(true)
+    fn source(&self) -> Result<&GeoIpSource> {
+        self.source.as_ref().context("No GeoIP source configured")
+    }
+
+    /// File next to the database holding the ETag of the source it was last checked against
+    fn etag_path(&self) -> PathBuf {
+        self.database.db_path.with_extension("mmdb.etag")
+    }
+
+    fn store_etag(&self, etag: Option<&str>) {
+        let result = match etag {
+            Some(etag) => fs::write(self.etag_path(), etag),
+            None => fs::remove_file(self.etag_path()).or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }),
+        };
+        if let Err(e) = result {
+            warn!("Failed to store {} database ETag: {}", self.database.edition, e);
+        }
+    }
+
+    /// Compares the source's modification time against the local file modified time.
+    /// Sources without Last-Modified are compared by ETag instead.
+    async fn is_update_needed(&self) -> Result<UpdateCheck> {
+        let local_exists = self.database.db_path.exists();
+        if !local_exists {
+            info!("Local database file not found at {:?}. Update needed.", self.database.db_path);
+        }
+
+        let remote_time = match self.source()? {
+            GeoIpSource::Http { url_template, auth, .. } => {
+                let url = GeoIpSource::url_for(url_template, &self.database.edition);
+                debug!("Sending HEAD request to {}", url);
+                let response = GeoIpSource::authorize(auth, self.client.head(&url))
+                    .send()
+                    .await?;
+
+                if !response.status().is_success() {
+                    anyhow::bail!("HEAD request failed: {}", response.status());
+                }
+
+                let etag = response.headers().get(header::ETAG)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+
+                match response.headers().get(header::LAST_MODIFIED) {
+                    Some(value) => {
+                        let remote_last_modified_str = value
+                            .to_str()
+                            .context("Last-Modified header is not valid UTF-8")?;
+                        parse_http_date(remote_last_modified_str)
+                            .context("Failed to parse Last-Modified header date")?
+                    }
+                    None => {
+                        let stored_etag = fs::read_to_string(self.etag_path()).ok();
+                        return Ok(match etag {
+                            Some(etag) if local_exists && stored_etag.as_deref() == Some(etag.as_str()) => {
+                                debug!("Source ETag {} is unchanged.", etag);
+                                UpdateCheck::UpToDate
+                            }
+                            // Without either header the content is compared after download instead
+                            etag => {
+                                debug!("No Last-Modified header and a new or missing ETag, assuming update needed.");
+                                UpdateCheck::Needed { etag }
+                            }
+                        });
+                    }
+                }
             }
-            Err(e) => {
-                Err(e).context("Failed to get local file metadata")
+            GeoIpSource::LocalDirectory { dir, format } => {
+                let source_path = GeoIpSource::local_path_for(dir, *format, &self.database.edition);
+                fs::metadata(&source_path)
+                    .and_then(|metadata| metadata.modified())
+                    .with_context(|| format!("Failed to read source database file {:?}", source_path))?
             }
+        };
+        debug!("Source database modified: {:?}", remote_time);
+
+        if !local_exists {
+            return Ok(UpdateCheck::Needed { etag: None });
         }
+
+        let local_time = fs::metadata(&self.database.db_path)
+            .and_then(|metadata| metadata.modified())
+            .context("Failed to get local file modification time")?;
+        debug!("Local database modified: {:?}", local_time);
+
+        Ok(if remote_time > local_time { UpdateCheck::Needed { etag: None } } else { UpdateCheck::UpToDate })
     }
 
-    /// Downloads, decompresses, extracts, and replaces the database file.
-    async fn download_and_replace(&self) -> Result<Reader<Vec<u8>>> {
-        let compressed_data = self.download_archive().await
+    /// Fetches, decompresses, extracts, and replaces the database file.
+    /// Returns `None` if the fetched database is identical to the local file.
+    async fn download_and_replace(&self) -> Result<Option<Reader<Vec<u8>>>> {
+        let source = self.source()?;
+        let archive_data = self.fetch_archive(source).await
             .context("Failed during database archive download")?;
 
-        let decompressed_data = self.extract_mmdb_from_archive(&compressed_data)
+        if let Some(checksum_file) = self.fetch_checksum(source).await
+            .context("Failed to download database checksum")?
+        {
+            verify_checksum(&archive_data, &checksum_file)?;
+            debug!("Database archive SHA256 verified.");
+        }
+
+        let decompressed_data = Self::extract_mmdb(&archive_data, source.format())
             .context("Failed to extract mmdb data from archive")?;
 
+        if fs::read(&self.database.db_path).is_ok_and(|existing| existing == decompressed_data) {
+            return Ok(None);
+        }
+
         let new_reader = Reader::from_source(decompressed_data.clone())
             .context("Failed to load extracted mmdb data into reader")?;
+
+        let current_build_epoch = match self.current_build_epoch.load(Ordering::Relaxed) {
+            0 => None,
+            epoch => Some(epoch),
+        };
+        verify_database(&new_reader, &self.database.edition, current_build_epoch, &self.probe_ips)?;
         debug!("Successfully validated downloaded database content.");
 
         self.replace_database_file(&decompressed_data)
             .context("Failed to replace database file")?;
 
-        Ok(new_reader)
+        self.ensure_installed_readable()?;
+
+        Ok(Some(new_reader))
     }
 
-    /// Performs the actual download GET request.
-    async fn download_archive(&self) -> Result<Bytes> {
-        let account_id = self.config.maxmind_account_id.as_ref().unwrap();
-        let license_key = self.config.maxmind_license_key.as_ref().unwrap();
+    /// Makes sure the file that went live can be read back, otherwise restores the previous version
+    fn ensure_installed_readable(&self) -> Result<()> {
+        if let Err(e) = Reader::open_readfile(&self.database.db_path) {
+            error!("Installed {} database is unreadable ({}), rolling back.", self.database.edition, e);
+            self.rollback().context("Failed to roll back database file")?;
+            anyhow::bail!("Installed database could not be read back: {}", e);
+        }
+        Ok(())
+    }
+
+    /// Performs the actual download GET request, or reads the file from the local source directory.
+    async fn fetch_archive(&self, source: &GeoIpSource) -> Result<Bytes> {
+        match source {
+            GeoIpSource::Http { url_template, auth, .. } => {
+                let url = GeoIpSource::url_for(url_template, &self.database.edition);
+                debug!("Downloading database archive from {}", url);
+                let response = GeoIpSource::authorize(auth, self.client.get(&url))
+                    .send()
+                    .await?;
+
+                if !response.status().is_success() {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
+                    anyhow::bail!("Download request failed: {} - {}", status, body);
+                }
 
-        debug!("Downloading database archive from {}", GEOIP_DATABASE_URL);
-        let response = self.client
-            .get(GEOIP_DATABASE_URL)
-            .basic_auth(account_id, Some(license_key))
+                let content = response.bytes().await?;
+                debug!("Downloaded {} bytes.", content.len());
+                Ok(content)
+            }
+            GeoIpSource::LocalDirectory { dir, format } => {
+                let source_path = GeoIpSource::local_path_for(dir, *format, &self.database.edition);
+                debug!("Reading database from local source {:?}", source_path);
+                let content = tokio::fs::read(&source_path).await
+                    .with_context(|| format!("Failed to read source database file {:?}", source_path))?;
+                Ok(Bytes::from(content))
+            }
+        }
+    }
+
+    /// Downloads the published checksum for the archive, if the source provides one.
+    async fn fetch_checksum(&self, source: &GeoIpSource) -> Result<Option<String>> {
+        let GeoIpSource::Http { checksum_url_template: Some(checksum_url_template), auth, .. } = source else {
+            return Ok(None);
+        };
+
+        let url = GeoIpSource::url_for(checksum_url_template, &self.database.edition);
+        debug!("Downloading database checksum from {}", url);
+        let response = GeoIpSource::authorize(auth, self.client.get(&url))
             .send()
             .await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
-            anyhow::bail!("Download request failed: {} - {}", status, body);
+            anyhow::bail!("Checksum request failed: {}", response.status());
         }
 
-        let content = response.bytes().await?;
-        debug!("Downloaded {} compressed bytes.", content.len());
-        Ok(content)
+        Ok(Some(response.text().await?))
+    }
+
+    /// Helper: Unpacks .mmdb data according to the source's archive format.
+    fn extract_mmdb(archive_data: &Bytes, format: ArchiveFormat) -> Result<Vec<u8>> {
+        match format {
+            ArchiveFormat::TarGz => Self::extract_mmdb_from_tar_gz(archive_data),
+            ArchiveFormat::Gz => {
+                debug!("Decompressing gzip layer...");
+                let mut buffer = Vec::new();
+                GzDecoder::new(Cursor::new(archive_data)).read_to_end(&mut buffer)?;
+                debug!("Extracted {} bytes of mmdb data.", buffer.len());
+                Ok(buffer)
+            }
+            ArchiveFormat::Mmdb => Ok(archive_data.to_vec()),
+        }
     }
 
     /// Helper: Decompresses gzip and extracts .mmdb data from tar archive bytes.
-    fn extract_mmdb_from_archive(&self, compressed_data: &Bytes) -> Result<Vec<u8>> {
+    fn extract_mmdb_from_tar_gz(compressed_data: &Bytes) -> Result<Vec<u8>> {
         debug!("Decompressing gzip layer...");
         let tar_data = GzDecoder::new(Cursor::new(compressed_data));
         
@@ -189,7 +513,7 @@ impl GeoIpUpdater {
             let mut entry = entry_result?;
             let path = entry.path()?.into_owned();
 
-            if path.extension().map_or(false, |ext| ext == "mmdb") {
+            if path.extension().is_some_and(|ext| ext == "mmdb") {
                 debug!("Found .mmdb file in archive: {:?}", path);
                 let mut buffer = Vec::with_capacity(entry.size() as usize);
                 entry.read_to_end(&mut buffer)?;
@@ -203,21 +527,194 @@ impl GeoIpUpdater {
         Ok(data)
     }
 
-    /// Writes data to a temp file and atomically renames it.
+    fn backup_path(&self) -> PathBuf {
+        self.database.db_path.with_extension("mmdb.bak")
+    }
+
+    /// Writes data to a temp file, keeps the current file as a backup and atomically renames it.
     fn replace_database_file(&self, data: &[u8]) -> Result<()> {
-        let temp_path = self.db_path.with_extension("mmdb.tmp");
+        let temp_path = self.database.db_path.with_extension("mmdb.tmp");
         debug!("Writing new database to temp file: {:?}", temp_path);
         
         // Ensure parent directory exists
-        if let Some(parent) = self.db_path.parent() {
+        if let Some(parent) = self.database.db_path.parent() {
             fs::create_dir_all(parent)?;
         }
         fs::write(&temp_path, data)?;
+
+        if self.database.db_path.exists() {
+            let backup_path = self.backup_path();
+            debug!("Keeping previous database as: {:?}", backup_path);
+            fs::copy(&self.database.db_path, &backup_path)?;
+        }
         
-        debug!("Atomically renaming temp file to: {:?}", self.db_path);
-        fs::rename(&temp_path, &self.db_path)?;
+        debug!("Atomically renaming temp file to: {:?}", self.database.db_path);
+        fs::rename(&temp_path, &self.database.db_path)?;
         
-        info!("Replaced database file at {:?}", self.db_path);
+        info!("Replaced database file at {:?}", self.database.db_path);
         Ok(())
     }
-} 
\ No newline at end of file
+
+    /// Restores the previous database file kept by `replace_database_file`.
+    fn rollback(&self) -> Result<()> {
+        let backup_path = self.backup_path();
+        if !backup_path.exists() {
+            anyhow::bail!("No previous database available at {:?}", backup_path);
+        }
+
+        let temp_path = self.database.db_path.with_extension("mmdb.tmp");
+        fs::copy(&backup_path, &temp_path)?;
+        fs::rename(&temp_path, &self.database.db_path)?;
+
+        warn!("Rolled back database file at {:?} to the previous version", self.database.db_path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use super::source::SourceAuth;
+    use axum::{Router, extract::State, http::{HeaderMap, Method}, routing::get};
+    use std::sync::atomic::AtomicUsize;
+
+    const EDITION: &str = "GeoLite2-Country";
+
+    /// Local HTTP mirror serving a single database with an ETag and no Last-Modified header
+    #[derive(Default)]
+    struct Mirror {
+        database: Mutex<(Vec<u8>, String)>,
+        downloads: AtomicUsize,
+    }
+
+    async fn serve_database(State(mirror): State<Arc<Mirror>>, method: Method) -> (HeaderMap, Vec<u8>) {
+        if method == Method::GET {
+            mirror.downloads.fetch_add(1, Ordering::SeqCst);
+        }
+        let (database, etag) = mirror.database.lock().unwrap().clone();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, etag.parse().unwrap());
+        (headers, database)
+    }
+
+    fn encode_string(value: &str) -> Vec<u8> {
+        let mut bytes = vec![0x40 | value.len() as u8];
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    /// Minimal IPv4 country database where 0.0.0.0/1 maps to an empty record and 128.0.0.0/1 is not found
+    pub(crate) fn test_database(build_epoch: u64) -> Vec<u8> {
+        test_database_of_type(EDITION, build_epoch)
+    }
+
+    pub(super) fn test_database_of_type(database_type: &str, build_epoch: u64) -> Vec<u8> {
+        // One search tree node with two 24 bit records, the first pointing at the first data section entry
+        // and the second equal to the node count, meaning no data
+        let mut db = vec![0, 0, 17, 0, 0, 1];
+        db.extend_from_slice(&[0; 16]);
+        db.push(0xE0);
+
+        db.extend_from_slice(b"\xAB\xCD\xEFMaxMind.com");
+        db.push(0xE0 | 9);
+        let mut field = |key: &str, value: &[u8]| {
+            db.extend(encode_string(key));
+            db.extend_from_slice(value);
+        };
+        field("node_count", &[0xC1, 1]);
+        field("record_size", &[0xA1, 24]);
+        field("ip_version", &[0xA1, 4]);
+        field("database_type", &encode_string(database_type));
+        field("languages", &[0x00, 0x04]);
+        field("binary_format_major_version", &[0xA1, 2]);
+        field("binary_format_minor_version", &[0xA0]);
+        field("build_epoch", &[[0x08, 0x02].as_slice(), &build_epoch.to_be_bytes()].concat());
+        field("description", &[0xE0]);
+        db
+    }
+
+    #[tokio::test]
+    async fn updates_from_local_http_mirror_using_etag() {
+        let dir = std::env::temp_dir().join(format!("betterlytics-geoip-updater-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mirror = Arc::new(Mirror::default());
+        *mirror.database.lock().unwrap() = (test_database(1), "\"v1\"".to_string());
+
+        let app = Router::new()
+            .route(&format!("/{}.mmdb", EDITION), get(serve_database))
+            .with_state(mirror.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let source = GeoIpSource::Http {
+            url_template: format!("http://{}/{{edition}}.mmdb", address),
+            checksum_url_template: None,
+            auth: SourceAuth::None,
+            format: ArchiveFormat::Mmdb,
+        };
+        let database = GeoIpDatabase { edition: EDITION.to_string(), db_path: dir.join("GeoLite2-Country.mmdb"), enabled: true };
+        let probe_ips = vec!["1.1.1.1".parse().unwrap()];
+        let (updater, mut watch_rx) = GeoIpUpdater::with_source(database, Some(source), Duration::from_secs(3600), probe_ips, None).unwrap();
+
+        let status = updater.check_and_update().await;
+        assert_eq!(status.last_outcome, Some("updated"), "{:?}", status.last_error);
+        assert_eq!(status.current_build_epoch, Some(1));
+        assert!(watch_rx.borrow_and_update().is_some());
+
+        // Same ETag, so the database is not downloaded again
+        let status = updater.check_and_update().await;
+        assert_eq!(status.last_outcome, Some("up_to_date"));
+        assert_eq!(mirror.downloads.load(Ordering::SeqCst), 1);
+
+        *mirror.database.lock().unwrap() = (test_database(2), "\"v2\"".to_string());
+        let status = updater.check_and_update().await;
+        assert_eq!(status.last_outcome, Some("updated"), "{:?}", status.last_error);
+        assert_eq!(status.current_build_epoch, Some(2));
+        assert_eq!(mirror.downloads.load(Ordering::SeqCst), 2);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    fn test_updater(dir: &std::path::Path, source: Option<GeoIpSource>) -> GeoIpUpdater {
+        let database = GeoIpDatabase { edition: EDITION.to_string(), db_path: dir.join("GeoLite2-Country.mmdb"), enabled: true };
+        GeoIpUpdater::with_source(database, source, Duration::from_secs(3600), Vec::new(), None).unwrap().0
+    }
+
+    #[test]
+    fn restores_backup_when_installed_database_is_unreadable() {
+        let dir = std::env::temp_dir().join(format!("betterlytics-geoip-rollback-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let updater = test_updater(&dir, None);
+
+        fs::write(&updater.database.db_path, test_database(1)).unwrap();
+        updater.replace_database_file(b"not a database").unwrap();
+        assert_eq!(fs::read(updater.backup_path()).unwrap(), test_database(1));
+
+        let error = updater.ensure_installed_readable().unwrap_err();
+        assert!(error.to_string().contains("could not be read back"), "{:#}", error);
+        assert_eq!(fs::read(&updater.database.db_path).unwrap(), test_database(1));
+        assert!(updater.ensure_installed_readable().is_ok());
+
+        // Without a backup the unreadable file stays and the rollback error is reported
+        fs::remove_file(updater.backup_path()).unwrap();
+        fs::write(&updater.database.db_path, b"not a database").unwrap();
+        let error = updater.ensure_installed_readable().unwrap_err();
+        assert!(format!("{:#}", error).contains("No previous database"), "{:#}", error);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn skips_update_without_source() {
+        let dir = std::env::temp_dir().join(format!("betterlytics-geoip-unconfigured-{}", std::process::id()));
+        let updater = test_updater(&dir, None);
+
+        assert!(!updater.is_configured());
+        let status = updater.check_and_update().await;
+        assert_eq!(status.last_outcome, Some("not_configured"));
+        assert!(status.last_check.is_none());
+        assert!(status.last_error.is_none());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::config::Config;
+use anyhow::{Result, Context};
+use chrono::{Datelike, Utc};
+use reqwest::RequestBuilder;
+use std::path::{Path, PathBuf};
+
+const MAXMIND_DATABASE_URL_TEMPLATE: &str = "https://download.maxmind.com/geoip/databases/{edition}/download?suffix=tar.gz";
+const MAXMIND_CHECKSUM_URL_TEMPLATE: &str = "https://download.maxmind.com/geoip/databases/{edition}/download?suffix=tar.gz.sha256";
+
+/// How credentials are attached to download requests
+#[derive(Debug, Clone)]
+pub enum SourceAuth {
+    None,
+    Basic { username: String, password: String },
+    Bearer { token: String },
+    /// Token sent as a query parameter (e.g. IPinfo's `?token=`)
+    QueryToken { param: String, token: String },
+}
+
+/// Packaging of the downloaded database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// Gzipped tar archive containing a .mmdb file (MaxMind)
+    TarGz,
+    /// Gzipped .mmdb file (DB-IP Lite)
+    Gz,
+    /// Plain .mmdb file (IPinfo, local mirrors)
+    Mmdb,
+}
+
+impl ArchiveFormat {
+    fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            "gz" | "mmdb.gz" => Ok(Self::Gz),
+            "mmdb" => Ok(Self::Mmdb),
+            other => anyhow::bail!("Unknown GeoIP archive format '{}'. Expected tar.gz, gz or mmdb", other),
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::Gz => "mmdb.gz",
+            Self::Mmdb => "mmdb",
+        }
+    }
+}
+
+/// Where a `GeoIpUpdater` fetches new database versions from
+#[derive(Debug, Clone)]
+pub enum GeoIpSource {
+    /// Remote HTTP(S) download. The URL may contain `{edition}`, `{year}` and `{month}` placeholders.
+    Http {
+        url_template: String,
+        /// URL of a `sha256sum` style checksum file for the downloaded archive
+        checksum_url_template: Option<String>,
+        auth: SourceAuth,
+        format: ArchiveFormat,
+    },
+    /// Directory that is populated out of band (e.g. by geoipupdate or a sync job),
+    /// containing files named `{edition}.{extension}`
+    LocalDirectory {
+        dir: PathBuf,
+        format: ArchiveFormat,
+    },
+}
+
+impl GeoIpSource {
+    /// Builds the configured source. Returns `None` if no usable source is configured.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let format = config.geoip_source_format.as_deref()
+            .map(ArchiveFormat::parse)
+            .transpose()?;
+
+        match config.geoip_source.to_lowercase().as_str() {
+            "maxmind" => {
+                let (Some(account_id), Some(license_key)) = (&config.maxmind_account_id, &config.maxmind_license_key) else {
+                    return Ok(None);
+                };
+                Ok(Some(Self::Http {
+                    url_template: MAXMIND_DATABASE_URL_TEMPLATE.to_string(),
+                    checksum_url_template: Some(MAXMIND_CHECKSUM_URL_TEMPLATE.to_string()),
+                    auth: SourceAuth::Basic {
+                        username: account_id.clone(),
+                        password: license_key.clone(),
+                    },
+                    format: format.unwrap_or(ArchiveFormat::TarGz),
+                }))
+            }
+            "http" => {
+                let url_template = config.geoip_source_url.clone()
+                    .context("GEOIP_SOURCE_URL is required for the http GeoIP source")?;
+                Ok(Some(Self::Http {
+                    url_template,
+                    checksum_url_template: config.geoip_source_checksum_url.clone(),
+                    auth: Self::auth_from_config(config)?,
+                    format: format.unwrap_or(ArchiveFormat::Mmdb),
+                }))
+            }
+            "local" => {
+                let dir = config.geoip_local_dir.clone()
+                    .context("GEOIP_LOCAL_DIR is required for the local GeoIP source")?;
+                Ok(Some(Self::LocalDirectory {
+                    dir,
+                    format: format.unwrap_or(ArchiveFormat::Mmdb),
+                }))
+            }
+            other => anyhow::bail!("Unknown GeoIP source '{}'. Expected maxmind, http or local", other),
+        }
+    }
+
+    fn auth_from_config(config: &Config) -> Result<SourceAuth> {
+        let secret = || config.geoip_source_token.clone()
+            .context("GEOIP_SOURCE_TOKEN is required for the configured GeoIP source auth");
+
+        match config.geoip_source_auth.to_lowercase().as_str() {
+            "none" => Ok(SourceAuth::None),
+            "basic" => Ok(SourceAuth::Basic {
+                username: config.geoip_source_username.clone()
+                    .context("GEOIP_SOURCE_USERNAME is required for basic GeoIP source auth")?,
+                password: secret()?,
+            }),
+            "bearer" => Ok(SourceAuth::Bearer { token: secret()? }),
+            "query" => Ok(SourceAuth::QueryToken {
+                param: config.geoip_source_token_param.clone(),
+                token: secret()?,
+            }),
+            other => anyhow::bail!("Unknown GeoIP source auth '{}'. Expected none, basic, bearer or query", other),
+        }
+    }
+
+    pub fn format(&self) -> ArchiveFormat {
+        match self {
+            Self::Http { format, .. } | Self::LocalDirectory { format, .. } => *format,
+        }
+    }
+
+    /// Expands the URL template for the given edition
+    pub fn url_for(url_template: &str, edition: &str) -> String {
+        let now = Utc::now();
+        url_template
+            .replace("{edition}", edition)
+            .replace("{year}", &now.year().to_string())
+            .replace("{month}", &format!("{:02}", now.month()))
+    }
+
+    /// Path of the database file inside a local source directory
+    pub fn local_path_for(dir: &Path, format: ArchiveFormat, edition: &str) -> PathBuf {
+        dir.join(format!("{}.{}", edition, format.file_extension()))
+    }
+
+    /// Attaches the configured credentials to a request
+    pub fn authorize(auth: &SourceAuth, request: RequestBuilder) -> RequestBuilder {
+        match auth {
+            SourceAuth::None => request,
+            SourceAuth::Basic { username, password } => request.basic_auth(username, Some(password)),
+            SourceAuth::Bearer { token } => request.bearer_auth(token),
+            SourceAuth::QueryToken { param, token } => request.query(&[(param, token)]),
+        }
+    }
+}
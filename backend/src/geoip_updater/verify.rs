@@ -0,0 +1,143 @@
+use maxminddb::Reader;
+use serde::de::IgnoredAny;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+
+/// Reasons a downloaded database is refused before it goes live
+#[derive(Debug, thiserror::Error)]
+pub enum IntegrityError {
+    #[error("checksum file is malformed: {0:?}")]
+    MalformedChecksum(String),
+    #[error("SHA256 mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("unexpected database type '{actual}', expected a {expected} database")]
+    UnexpectedDatabaseType { expected: String, actual: String },
+    #[error("database build epoch {new} is older than the current build {current}")]
+    StaleBuild { current: u64, new: u64 },
+    #[error("probe IP {0} does not resolve in the new database")]
+    ProbeFailed(IpAddr),
+}
+
+/// Verifies data against a `sha256sum` style checksum file ("<hex digest>  <file name>")
+pub fn verify_checksum(data: &[u8], checksum_file: &str) -> Result<(), IntegrityError> {
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .filter(|digest| digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| IntegrityError::MalformedChecksum(checksum_file.chars().take(100).collect()))?
+        .to_lowercase();
+
+    let actual = format!("{:x}", Sha256::digest(data));
+    if actual != expected {
+        return Err(IntegrityError::ChecksumMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// The database type an edition is expected to contain (e.g. "GeoLite2-City" -> "City")
+pub fn expected_database_type(edition: &str) -> Option<&'static str> {
+    ["City", "Country", "ASN"]
+        .into_iter()
+        .find(|kind| edition.to_lowercase().contains(&kind.to_lowercase()))
+}
+
+/// Sanity-checks a new database's metadata and contents before it replaces the current one
+pub fn verify_database(
+    reader: &Reader<Vec<u8>>,
+    edition: &str,
+    current_build_epoch: Option<u64>,
+    probe_ips: &[IpAddr],
+) -> Result<(), IntegrityError> {
+    let metadata = &reader.metadata;
+
+    if let Some(expected) = expected_database_type(edition)
+        && !metadata.database_type.to_lowercase().contains(&expected.to_lowercase())
+    {
+        return Err(IntegrityError::UnexpectedDatabaseType {
+            expected: expected.to_string(),
+            actual: metadata.database_type.clone(),
+        });
+    }
+
+    if let Some(current) = current_build_epoch
+        && metadata.build_epoch < current
+    {
+        return Err(IntegrityError::StaleBuild { current, new: metadata.build_epoch });
+    }
+
+    for ip in probe_ips {
+        match reader.lookup::<IgnoredAny>(*ip) {
+            Ok(Some(_)) => {}
+            _ => return Err(IntegrityError::ProbeFailed(*ip)),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geoip_updater::tests::{test_database, test_database_of_type};
+
+    const EDITION: &str = "GeoLite2-Country";
+
+    fn reader(data: Vec<u8>) -> Reader<Vec<u8>> {
+        Reader::from_source(data).unwrap()
+    }
+
+    #[test]
+    fn verifies_sha256sum_files() {
+        let digest = format!("{:x}", Sha256::digest(b"database"));
+
+        assert!(verify_checksum(b"database", &format!("{}  GeoLite2-Country.tar.gz\n", digest)).is_ok());
+        assert!(verify_checksum(b"database", &digest.to_uppercase()).is_ok());
+        assert!(matches!(
+            verify_checksum(b"tampered", &digest),
+            Err(IntegrityError::ChecksumMismatch { expected, .. }) if expected == digest
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_checksum_files() {
+        for checksum_file in ["", "   ", "abc123  file.mmdb", &"g".repeat(64), "<html>Not Found</html>"] {
+            assert!(
+                matches!(verify_checksum(b"database", checksum_file), Err(IntegrityError::MalformedChecksum(_))),
+                "{:?}",
+                checksum_file
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unexpected_database_type() {
+        let result = verify_database(&reader(test_database_of_type("GeoLite2-ASN", 1)), EDITION, None, &[]);
+
+        assert!(matches!(
+            result,
+            Err(IntegrityError::UnexpectedDatabaseType { expected, actual }) if expected == "Country" && actual == "GeoLite2-ASN"
+        ));
+    }
+
+    #[test]
+    fn rejects_builds_older_than_the_current_database() {
+        let database = reader(test_database(5));
+
+        assert!(matches!(
+            verify_database(&database, EDITION, Some(6), &[]),
+            Err(IntegrityError::StaleBuild { current: 6, new: 5 })
+        ));
+        assert!(verify_database(&database, EDITION, Some(5), &[]).is_ok());
+    }
+
+    #[test]
+    fn rejects_database_when_a_probe_ip_does_not_resolve() {
+        let database = reader(test_database(1));
+        let resolving: IpAddr = "8.8.8.8".parse().unwrap();
+        let missing: IpAddr = "203.0.113.1".parse().unwrap();
+
+        assert!(verify_database(&database, EDITION, None, &[resolving]).is_ok());
+        let result = verify_database(&database, EDITION, None, &[resolving, missing]);
+        assert!(matches!(result, Err(IntegrityError::ProbeFailed(ip)) if ip == missing), "{:?}", result);
+    }
+}
@@ -9,7 +9,16 @@ pub mod bot_detection;
 pub mod referrer;
 pub mod campaign;
 pub mod ua_parser;
+pub mod parser_updater;
+pub mod channel;
+pub mod site_config;
+pub mod normalization;
+pub mod properties;
+pub mod schema;
+pub mod cross_domain;
+pub mod revenue;
 pub mod metrics;
+pub mod admin;
 
 // Re-export commonly used types
 pub use analytics::{AnalyticsEvent, generate_site_id};
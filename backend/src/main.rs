@@ -1,5 +1,5 @@
 use axum::{
-    extract::{ConnectInfo, State}, http::{HeaderMap, StatusCode}, response::IntoResponse, routing::{get, post}, Json, Router
+    extract::{ConnectInfo, State}, http::{HeaderMap, HeaderName, HeaderValue, StatusCode}, middleware::map_response, response::{IntoResponse, Response}, routing::{get, post}, Json, Router
 };
 use std::sync::Arc;
 use std::{net::SocketAddr, net::IpAddr, str::FromStr};
@@ -18,14 +18,26 @@ mod bot_detection;
 mod referrer;
 mod campaign;
 mod ua_parser;
+mod parser_updater;
+mod channel;
+mod site_config;
+mod normalization;
+mod properties;
+mod schema;
+mod cross_domain;
+mod revenue;
 mod metrics;
+mod admin;
 
 use analytics::{AnalyticsEvent, RawTrackingEvent, generate_site_id};
 use db::{Database, SharedDatabase};
 use processing::EventProcessor;
 use geoip::GeoIpService;
-use geoip_updater::GeoIpUpdater;
+use geoip_updater::{GeoIpDatabase, GeoIpUpdater};
 use metrics::MetricsCollector;
+use parser_updater::ParserUpdater;
+use referrer::ReferrerSpamUpdater;
+use ua_parser::{ACCEPT_CH, ClientHints};
 
 #[tokio::main]
 async fn main() {
@@ -38,8 +50,18 @@ async fn main() {
 
     referrer::initialize(&config.referrer_db_path);
 
+    referrer::initialize_supplement(&config.referrer_supplement_path);
+
+    referrer::initialize_spam_list(&config.referrer_spam_list_path);
+
     ua_parser::initialize(&config.ua_regexes_path);
 
+    channel::initialize(&config.channel_rules_path);
+
+    site_config::initialize(config.site_config_path.as_deref());
+
+    revenue::initialize(config.exchange_rates_path.as_deref());
+
     let ip_addr = config.server_host.parse::<std::net::IpAddr>()
         .map_err(|e| format!("Invalid server host IP address '{}': {}", config.server_host, e))
         .expect("Failed to parse server host IP address");
@@ -47,31 +69,60 @@ async fn main() {
     let addr = SocketAddr::from((ip_addr, config.server_port));
     info!("Server starting on {}", addr);
 
-    let (updater, geoip_watch_rx) = GeoIpUpdater::new(config.clone())
+    let metrics_collector = if config.enable_monitoring {
+        let collector = MetricsCollector::new()
+            .expect("Failed to initialize metrics collector")
+            .start_system_metrics_updater();
+        info!("Metrics collector started");
+        Some(collector)
+    } else {
+        info!("Metrics collection disabled");
+        None
+    };
+
+    let (updater, geoip_watch_rx) = GeoIpUpdater::new(config.clone(), GeoIpDatabase::location(&config), metrics_collector.clone())
         .expect("Failed to create GeoIP updater");
     let updater = Arc::new(updater);
 
-    let geoip_service = GeoIpService::new(config.clone(), geoip_watch_rx)
+    let (asn_updater, asn_watch_rx) = GeoIpUpdater::new(config.clone(), GeoIpDatabase::asn(&config), metrics_collector.clone())
+        .expect("Failed to create ASN database updater");
+    let asn_updater = Arc::new(asn_updater);
+
+    let geoip_service = GeoIpService::new(config.clone(), geoip_watch_rx, asn_watch_rx)
         .expect("Failed to initialize GeoIP service");
 
     let _updater_handle = tokio::spawn(Arc::clone(&updater).run());
+    let _asn_updater_handle = tokio::spawn(Arc::clone(&asn_updater).run());
+
+    let spam_updater = Arc::new(
+        ReferrerSpamUpdater::new(config.clone()).expect("Failed to create referrer spam list updater")
+    );
+    let _spam_updater_handle = tokio::spawn(spam_updater.run());
+
+    let parser_updater = Arc::new(
+        ParserUpdater::new(config.clone()).expect("Failed to create parser data updater")
+    );
+    let _parser_watcher_handle = tokio::spawn(Arc::clone(&parser_updater).run_watcher());
+    let _parser_updater_handle = tokio::spawn(Arc::clone(&parser_updater).run_updater());
 
     let db = Database::new(config.clone()).await.expect("Failed to initialize database");
     db.validate_schema().await.expect("Invalid database schema");
     let db = Arc::new(db);
 
-    let metrics_collector = if config.enable_monitoring {
-        let collector = MetricsCollector::new()
-            .expect("Failed to initialize metrics collector")
-            .start_system_metrics_updater();
-        info!("Metrics collector started");
-        Some(collector)
-    } else {
-        info!("Metrics collection disabled");
-        None
-    };
+    #[cfg(unix)]
+    admin::spawn_sighup_handler(geoip_service.clone());
+
+    let admin_state = config.admin_api_token.clone().map(|token| admin::AdminState {
+        admin_token: Arc::new(token),
+        geoip_service: geoip_service.clone(),
+        geoip_updaters: vec![Arc::clone(&updater), Arc::clone(&asn_updater)],
+        parser_updater: Arc::clone(&parser_updater),
+        site_config_path: config.site_config_path.clone(),
+        channel_rules_path: config.channel_rules_path.clone(),
+        exchange_rates_path: config.exchange_rates_path.clone(),
+    });
 
-    let (processor, mut processed_rx) = EventProcessor::new(geoip_service);
+    let (processor, mut processed_rx) = EventProcessor::new(config.clone(), geoip_service, metrics_collector.clone());
     let processor = Arc::new(processor);
 
     let db_clone = db.clone();
@@ -83,13 +134,23 @@ async fn main() {
         }
     });
 
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/health", get(health_check))
-        .route("/track", post(track_event))
+        .route("/track", post(track_event).layer(map_response(add_accept_ch_header)))
+        .route("/cross-domain/token", post(cross_domain_token))
         .route("/site-id", get(generate_site_id_handler))
         .route("/metrics", get(metrics_handler))
-        .with_state((db, processor, metrics_collector))
-        .layer(CorsLayer::permissive());
+        .with_state((db, processor, metrics_collector));
+
+    match admin_state {
+        Some(admin_state) => {
+            app = app.merge(admin::router(admin_state));
+            info!("Admin endpoints enabled");
+        }
+        None => info!("Admin endpoints disabled (ADMIN_API_TOKEN not set)"),
+    }
+
+    let app = app.layer(CorsLayer::permissive());
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     info!("Listening on {}", addr);
@@ -115,7 +176,7 @@ async fn track_event(
     State((_db, processor, metrics)): State<(SharedDatabase, Arc<EventProcessor>, Option<Arc<MetricsCollector>>)>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
-    Json(raw_event): Json<RawTrackingEvent>,
+    Json(mut raw_event): Json<RawTrackingEvent>,
 ) -> Result<StatusCode, (StatusCode, String)> {
     if raw_event.site_id.is_empty() {
         return Err((StatusCode::BAD_REQUEST, "site_id is required".to_string()));
@@ -127,7 +188,9 @@ async fn track_event(
         return Err((StatusCode::BAD_REQUEST, "event name is required".to_string()));
     }
 
-    let event = AnalyticsEvent::new(raw_event, parse_ip(headers).unwrap_or(addr.ip()).to_string());
+    merge_header_client_hints(&mut raw_event, &headers);
+
+    let event = AnalyticsEvent::new(raw_event, parse_ip(headers).unwrap_or(addr.ip()));
 
     let start_time = std::time::Instant::now();
     if let Err(e) = processor.process_event(event).await {
@@ -144,6 +207,31 @@ async fn track_event(
     Ok(StatusCode::OK)
 }
 
+/// Issues a signed token the tracker appends to links to other domains of the same site
+async fn cross_domain_token(
+    State((_db, processor, _metrics)): State<(SharedDatabase, Arc<EventProcessor>, Option<Arc<MetricsCollector>>)>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(mut raw_event): Json<RawTrackingEvent>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if raw_event.site_id.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "site_id is required".to_string()));
+    }
+
+    merge_header_client_hints(&mut raw_event, &headers);
+
+    let event = AnalyticsEvent::new(raw_event, parse_ip(headers).unwrap_or(addr.ip()));
+
+    match processor.issue_cross_domain_token(&event) {
+        Ok(Some((token, expires_in))) => Ok(Json(serde_json::json!({ "token": token, "expires_in": expires_in }))),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "Cross-domain tracking is disabled or there is no active session".to_string())),
+        Err(e) => {
+            error!("Failed to issue cross-domain token: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to issue token".to_string()))
+        }
+    }
+}
+
 async fn metrics_handler(
     State((_, _, metrics)): State<(SharedDatabase, Arc<EventProcessor>, Option<Arc<MetricsCollector>>)>,
 ) -> impl IntoResponse {
@@ -161,6 +249,25 @@ async fn metrics_handler(
     }
 }
 
+/// Fill client hints missing from the tracker payload with `Sec-CH-UA-*` request headers
+fn merge_header_client_hints(raw_event: &mut RawTrackingEvent, headers: &HeaderMap) {
+    if let Some(header_hints) = ClientHints::from_headers(headers) {
+        raw_event.client_hints = Some(match raw_event.client_hints.take() {
+            Some(tracker_hints) => tracker_hints.merge(header_hints),
+            None => header_hints,
+        });
+    }
+}
+
+/// Ask browsers to send User-Agent Client Hints on subsequent tracking requests
+async fn add_accept_ch_header(mut response: Response) -> Response {
+    response.headers_mut().insert(
+        HeaderName::from_static("accept-ch"),
+        HeaderValue::from_static(ACCEPT_CH),
+    );
+    response
+}
+
 pub fn parse_ip(headers: HeaderMap) -> Result<IpAddr, ()> {
     // Get IP from X-Forwarded-For header
     if let Some(forwarded_for) = headers.get("x-forwarded-for") {
@@ -1,5 +1,5 @@
 use prometheus::{
-    Encoder, Gauge, Histogram, HistogramOpts, IntCounter, Opts, Registry, TextEncoder,
+    Encoder, Gauge, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
 };
 use std::sync::Arc;
 use std::time::Duration;
@@ -21,6 +21,14 @@ pub struct MetricsCollector {
     process_memory_usage: Gauge,
     events_processed_total: IntCounter,
     events_processing_duration: Histogram,
+    custom_event_properties_rejected_total: IntCounterVec,
+    custom_event_schema_violations_total: IntCounterVec,
+    revenue_events_total: IntCounterVec,
+
+    // GeoIP database update metrics
+    geoip_update_attempts_total: IntCounterVec,
+    geoip_database_build_epoch: GaugeVec,
+    geoip_last_update_check: GaugeVec,
 
     // System info
     system: Arc<RwLock<System>>,
@@ -66,6 +74,54 @@ impl MetricsCollector {
             "Time spent processing analytics events"
         ))?;
         
+        let custom_event_properties_rejected_total = IntCounterVec::new(
+            Opts::new(
+                "analytics_custom_event_properties_rejected_total",
+                "Custom event properties dropped during validation by reason"
+            ),
+            &["reason"]
+        )?;
+
+        let custom_event_schema_violations_total = IntCounterVec::new(
+            Opts::new(
+                "analytics_custom_event_schema_violations_total",
+                "Custom event schema violations by kind and action taken (tagged or rejected)"
+            ),
+            &["kind", "action"]
+        )?;
+
+        let revenue_events_total = IntCounterVec::new(
+            Opts::new(
+                "analytics_revenue_events_total",
+                "Events with revenue by result (accepted, unconverted, duplicate_order or the validation failure)"
+            ),
+            &["result"]
+        )?;
+
+        let geoip_update_attempts_total = IntCounterVec::new(
+            Opts::new(
+                "geoip_update_attempts_total",
+                "GeoIP database update attempts by edition and result"
+            ),
+            &["edition", "result"]
+        )?;
+
+        let geoip_database_build_epoch = GaugeVec::new(
+            Opts::new(
+                "geoip_database_build_epoch_seconds",
+                "Build epoch of the live GeoIP database"
+            ),
+            &["edition"]
+        )?;
+
+        let geoip_last_update_check = GaugeVec::new(
+            Opts::new(
+                "geoip_last_update_check_timestamp_seconds",
+                "Unix timestamp of the last GeoIP database update check"
+            ),
+            &["edition"]
+        )?;
+
         registry.register(Box::new(system_cpu_usage.clone()))?;
         registry.register(Box::new(system_memory_usage.clone()))?;
         registry.register(Box::new(system_memory_total.clone()))?;
@@ -73,6 +129,12 @@ impl MetricsCollector {
         registry.register(Box::new(process_memory_usage.clone()))?;
         registry.register(Box::new(events_processed_total.clone()))?;
         registry.register(Box::new(events_processing_duration.clone()))?;
+        registry.register(Box::new(custom_event_properties_rejected_total.clone()))?;
+        registry.register(Box::new(custom_event_schema_violations_total.clone()))?;
+        registry.register(Box::new(revenue_events_total.clone()))?;
+        registry.register(Box::new(geoip_update_attempts_total.clone()))?;
+        registry.register(Box::new(geoip_database_build_epoch.clone()))?;
+        registry.register(Box::new(geoip_last_update_check.clone()))?;
         
         let mut system = System::new_all();
         system.refresh_all(); // This refresh is an attempt to ensure that when the metrics_updater starts it has accurate initial values
@@ -89,6 +151,12 @@ impl MetricsCollector {
             process_memory_usage,
             events_processed_total,
             events_processing_duration,
+            custom_event_properties_rejected_total,
+            custom_event_schema_violations_total,
+            revenue_events_total,
+            geoip_update_attempts_total,
+            geoip_database_build_epoch,
+            geoip_last_update_check,
             system: Arc::new(RwLock::new(system)),
             current_pid,
         };
@@ -155,6 +223,29 @@ impl MetricsCollector {
         self.events_processing_duration.observe(duration.as_secs_f64());
     }
     
+    pub fn record_property_rejection(&self, reason: &str) {
+        self.custom_event_properties_rejected_total.with_label_values(&[reason]).inc();
+    }
+
+    pub fn record_schema_violation(&self, kind: &str, action: &str) {
+        self.custom_event_schema_violations_total.with_label_values(&[kind, action]).inc();
+    }
+
+    pub fn record_revenue_event(&self, result: &str) {
+        self.revenue_events_total.with_label_values(&[result]).inc();
+    }
+
+    pub fn record_geoip_update(&self, edition: &str, result: &str) {
+        self.geoip_update_attempts_total.with_label_values(&[edition, result]).inc();
+        self.geoip_last_update_check
+            .with_label_values(&[edition])
+            .set(chrono::Utc::now().timestamp() as f64);
+    }
+
+    pub fn set_geoip_build_epoch(&self, edition: &str, build_epoch: u64) {
+        self.geoip_database_build_epoch.with_label_values(&[edition]).set(build_epoch as f64);
+    }
+    
     pub fn export_metrics(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
         let encoder = TextEncoder::new();
         let metric_families = self.registry.gather();
@@ -0,0 +1,311 @@
+use anyhow::{Result, Context};
+use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
+use url::{Url, form_urlencoded};
+
+/// Query parameters that are never stored, even when allowlisted, since they commonly carry personal data or credentials.
+/// Compared against the lowercased parameter name without separators, so "e-mail" matches "email".
+const PII_PARAMS: &[&str] = &[
+    "mail", "tel", "name", "auth", "code", "key", "pass", "pwd", "session", "sid", "ssn",
+];
+
+/// Like `PII_PARAMS`, but also matched anywhere in the parameter name, e.g. "user_email" or "authToken"
+const PII_PATTERNS: &[&str] = &[
+    "email", "phone", "firstname", "lastname", "fullname", "username", "address",
+    "token", "apikey", "password", "passwd", "secret", "sessionid",
+];
+
+/// What to do with a trailing slash on non-root paths
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingSlash {
+    #[default]
+    Keep,
+    Strip,
+    Add,
+}
+
+/// A path template that collapses dynamic paths into a single page. Like `data-dynamic-urls` in the tracker,
+/// templates match a path prefix and the rest of the path is kept after the template.
+/// Either `pattern` (a glob with `*`, `**` and `:name` segments) or `regex` (with `template`) is required.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PathTemplateDefinition {
+    /// Glob pattern, e.g. "/users/:id" or "/blog/**"
+    pub pattern: Option<String>,
+    /// Regex matched against the start of the path, e.g. "^/products/(?P<category>[a-z-]+)/[a-z0-9-]+"
+    pub regex: Option<String>,
+    /// Replacement for the matched part of the path. Defaults to the glob pattern with `**` shown as `*`.
+    /// `$name` inserts the value of a `:name` segment or named regex group, e.g. "/products/$category/*".
+    pub template: Option<String>,
+}
+
+/// Per-site URL normalization settings as written in the site config file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UrlNormalizationSettings {
+    #[serde(default)]
+    pub lowercase: bool,
+    #[serde(default)]
+    pub trailing_slash: TrailingSlash,
+    /// File names removed from the end of paths, e.g. ["index.html", "index.php"]
+    #[serde(default)]
+    pub index_files: Vec<String>,
+    /// Evaluated in order, the first matching template wins
+    #[serde(default)]
+    pub templates: Vec<PathTemplateDefinition>,
+    /// Query parameters kept in the stored URL, e.g. ["q", "page"]. All others are dropped.
+    #[serde(default)]
+    pub query_params: Vec<String>,
+    /// Treat fragments starting with "/" or "!/" as the path, for hash-routed single page apps
+    #[serde(default)]
+    pub fragment_as_path: bool,
+}
+
+#[derive(Debug)]
+struct PathTemplate {
+    regex: Regex,
+    template: String,
+}
+
+/// Compiled URL normalization rules of a site
+#[derive(Debug, Default)]
+pub struct UrlNormalizer {
+    lowercase: bool,
+    trailing_slash: TrailingSlash,
+    index_files: Vec<String>,
+    templates: Vec<PathTemplate>,
+    query_params: Vec<String>,
+    fragment_as_path: bool,
+}
+
+impl UrlNormalizer {
+    pub fn compile(settings: UrlNormalizationSettings) -> Result<Self> {
+        let templates = settings.templates
+            .into_iter()
+            .map(PathTemplate::compile)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            lowercase: settings.lowercase,
+            trailing_slash: settings.trailing_slash,
+            index_files: settings.index_files,
+            templates,
+            query_params: settings.query_params
+                .into_iter()
+                .map(|param| param.trim().to_lowercase())
+                .filter(|param| !param.is_empty() && !is_pii_param(param))
+                .collect(),
+            fragment_as_path: settings.fragment_as_path,
+        })
+    }
+
+    /// Path and retained query string of a page URL. With `fragment_as_path`, a route in the
+    /// fragment replaces the path and its own query string is considered as well.
+    pub fn normalize_url(&self, url: &Url) -> (String, String) {
+        let route = url.fragment()
+            .filter(|_| self.fragment_as_path)
+            .and_then(|fragment| fragment.strip_prefix('!').unwrap_or(fragment).strip_prefix('/'));
+
+        let (path, fragment_query) = match route {
+            Some(route) => {
+                let (route_path, route_query) = route.split_once('?').unwrap_or((route, ""));
+                (format!("/{}", route_path), route_query)
+            }
+            None if url.path().is_empty() => ("/".to_string(), ""),
+            None => (url.path().to_string(), ""),
+        };
+
+        let fragment_pairs = form_urlencoded::parse(fragment_query.as_bytes());
+        let query = self.retained_query(url.query_pairs().chain(fragment_pairs));
+
+        (self.normalize_path(&path), query)
+    }
+
+    /// Serialize the allowlisted parameters, dropping any value that looks like an email address
+    fn retained_query<'a>(&self, pairs: impl Iterator<Item = (Cow<'a, str>, Cow<'a, str>)>) -> String {
+        if self.query_params.is_empty() {
+            return String::new();
+        }
+
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        for (key, value) in pairs {
+            if self.query_params.contains(&key.to_lowercase()) && !looks_like_email(&value) {
+                serializer.append_pair(&key, &value);
+            }
+        }
+        serializer.finish()
+    }
+
+    /// Apply case folding, index-file stripping, the trailing-slash policy and path templates, in that order
+    pub fn normalize_path(&self, path: &str) -> String {
+        let mut path = if self.lowercase { path.to_lowercase() } else { path.to_string() };
+
+        for index_file in &self.index_files {
+            if path.ends_with(&format!("/{}", index_file)) {
+                path.truncate(path.len() - index_file.len());
+                break;
+            }
+        }
+
+        if path != "/" {
+            match self.trailing_slash {
+                TrailingSlash::Keep => {}
+                TrailingSlash::Strip => {
+                    let trimmed_len = path.trim_end_matches('/').len().max(1);
+                    path.truncate(trimmed_len);
+                }
+                TrailingSlash::Add if !path.ends_with('/') => path.push('/'),
+                TrailingSlash::Add => {}
+            }
+        }
+
+        self.templates
+            .iter()
+            .find_map(|template| template.apply(&path))
+            .unwrap_or(path)
+    }
+}
+
+impl PathTemplate {
+    fn compile(definition: PathTemplateDefinition) -> Result<Self> {
+        match (definition.pattern, definition.regex) {
+            (Some(pattern), None) => Ok(Self {
+                regex: Regex::new(&glob_to_regex(&pattern)?)
+                    .with_context(|| format!("Invalid path pattern '{}'", pattern))?,
+                template: definition.template.unwrap_or_else(|| pattern.replace("**", "*")),
+            }),
+            (None, Some(regex)) => {
+                let template = definition.template
+                    .with_context(|| format!("Path regex '{}' requires a template", regex))?;
+                Ok(Self {
+                    regex: Regex::new(&regex).with_context(|| format!("Invalid path regex '{}'", regex))?,
+                    template,
+                })
+            }
+            _ => anyhow::bail!("Path templates need exactly one of 'pattern' or 'regex'"),
+        }
+    }
+
+    /// Replace the matched prefix of the path with the template, keeping the remainder
+    fn apply(&self, path: &str) -> Option<String> {
+        let captures = self.regex.captures(path)?;
+        let matched = captures.get(0).filter(|m| m.start() == 0)?;
+        let rest = &path[matched.end()..];
+
+        // Only match whole segments, so "/blog" does not match "/blogging"
+        if !rest.is_empty() && !rest.starts_with('/') && !matched.as_str().ends_with('/') {
+            return None;
+        }
+
+        let mut normalized = String::new();
+        captures.expand(&self.template, &mut normalized);
+        normalized.push_str(rest);
+        Some(normalized)
+    }
+}
+
+fn is_pii_param(param: &str) -> bool {
+    let param: String = param.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+    PII_PARAMS.contains(&param.as_str()) || PII_PATTERNS.iter().any(|pattern| param.contains(pattern))
+}
+
+fn looks_like_email(value: &str) -> bool {
+    value.split_once('@').is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'))
+}
+
+/// Convert a path glob into a regex anchored at the start of the path: `**` matches any number of
+/// segments, `*` one segment, and a `:name` segment one segment captured as a named group
+fn glob_to_regex(pattern: &str) -> Result<String> {
+    let segments = pattern
+        .split('/')
+        .map(|segment| match segment {
+            "**" => Ok(".+".to_string()),
+            "*" => Ok("[^/]+".to_string()),
+            _ => match segment.strip_prefix(':') {
+                Some(name) => {
+                    anyhow::ensure!(
+                        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                        "Invalid segment name ':{}' in path pattern '{}', use letters, digits and underscores",
+                        name,
+                        pattern
+                    );
+                    Ok(format!("(?P<{}>[^/]+)", name))
+                }
+                None => Ok(regex::escape(segment).replace(r"\*", "[^/]*")),
+            },
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(format!("^{}", segments.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer(settings: serde_json::Value) -> UrlNormalizer {
+        UrlNormalizer::compile(serde_json::from_value(settings).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn glob_to_regex_converts_wildcards_and_named_segments() {
+        assert_eq!(glob_to_regex("/blog/**").unwrap(), "^/blog/.+");
+        assert_eq!(glob_to_regex("/users/*/posts").unwrap(), "^/users/[^/]+/posts");
+        assert_eq!(glob_to_regex("/users/:id").unwrap(), "^/users/(?P<id>[^/]+)");
+        assert_eq!(glob_to_regex("/files/*.pdf").unwrap(), r"^/files/[^/]*\.pdf");
+    }
+
+    #[test]
+    fn glob_to_regex_rejects_invalid_segment_names() {
+        assert!(glob_to_regex("/users/:foo-bar").is_err());
+        assert!(glob_to_regex("/users/:").is_err());
+    }
+
+    #[test]
+    fn normalize_path_applies_rules_in_order() {
+        let normalizer = normalizer(serde_json::json!({
+            "lowercase": true,
+            "trailing_slash": "strip",
+            "index_files": ["index.html"],
+            "templates": [{ "pattern": "/users/:id" }, { "pattern": "/blog/**" }],
+        }));
+
+        assert_eq!(normalizer.normalize_path("/About/"), "/about");
+        assert_eq!(normalizer.normalize_path("/docs/index.html"), "/docs");
+        assert_eq!(normalizer.normalize_path("/"), "/");
+        assert_eq!(normalizer.normalize_path("/Users/42/Settings"), "/users/:id/settings");
+        assert_eq!(normalizer.normalize_path("/blog/2024/05/post"), "/blog/*");
+        assert_eq!(normalizer.normalize_path("/blogging"), "/blogging");
+    }
+
+    #[test]
+    fn normalize_path_expands_captures_in_templates() {
+        let normalizer = normalizer(serde_json::json!({
+            "lowercase": true,
+            "templates": [
+                { "regex": "^/products/(?P<category>[a-z-]+)/[a-z0-9-]+", "template": "/products/$category/*" },
+                { "pattern": "/teams/:team/members/:id", "template": "/teams/$team/members/:id" },
+            ],
+        }));
+
+        assert_eq!(normalizer.normalize_path("/products/Shoes/SKU-123/reviews"), "/products/shoes/*/reviews");
+        assert_eq!(normalizer.normalize_path("/teams/core/members/7"), "/teams/core/members/:id");
+    }
+
+    #[test]
+    fn pii_params_are_matched_by_pattern() {
+        for param in ["email", "E-Mail", "user_email", "auth_token", "customer_phone", "firstName", "sid"] {
+            assert!(is_pii_param(param), "{}", param);
+        }
+        for param in ["q", "page", "keyword", "author", "sort"] {
+            assert!(!is_pii_param(param), "{}", param);
+        }
+    }
+
+    #[test]
+    fn add_trailing_slash_leaves_existing_slash() {
+        let normalizer = normalizer(serde_json::json!({ "trailing_slash": "add" }));
+        assert_eq!(normalizer.normalize_path("/pricing"), "/pricing/");
+        assert_eq!(normalizer.normalize_path("/pricing/"), "/pricing/");
+    }
+}
@@ -0,0 +1,212 @@
+use crate::config::Config;
+use crate::{referrer, ua_parser};
+use anyhow::{Result, Context};
+use reqwest::Client;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::time::interval;
+use tracing::{info, error, debug};
+
+/// Data files backing the user agent and referrer parsers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParserKind {
+    UserAgent,
+    Referrer,
+    ReferrerSupplement,
+}
+
+impl ParserKind {
+    fn index(self) -> usize {
+        match self {
+            ParserKind::UserAgent => 0,
+            ParserKind::Referrer => 1,
+            ParserKind::ReferrerSupplement => 2,
+        }
+    }
+
+    /// Validates and swaps in the parser data at the given path
+    fn reload(self, path: &Path) -> Result<()> {
+        match self {
+            ParserKind::UserAgent => ua_parser::reload(path),
+            ParserKind::Referrer => referrer::reload(path),
+            ParserKind::ReferrerSupplement => referrer::reload_supplement(path),
+        }
+    }
+
+    /// Validates a downloaded file without swapping it in
+    fn validate(self, path: &Path) -> Result<()> {
+        match self {
+            ParserKind::UserAgent => ua_parser::load_parser(path).map(|_| ()),
+            ParserKind::Referrer => referrer::load_referrer_db(path).map(|_| ()),
+            ParserKind::ReferrerSupplement => referrer::validate_supplement(path),
+        }
+    }
+}
+
+struct ParserFile {
+    kind: ParserKind,
+    path: PathBuf,
+    url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadResult {
+    pub parser: ParserKind,
+    pub path: PathBuf,
+    pub reloaded: bool,
+    pub error: Option<String>,
+}
+
+/// Reloads the user agent regexes and referrer databases when their files change on disk,
+/// and optionally downloads new versions from configured URLs.
+pub struct ParserUpdater {
+    client: Client,
+    files: [ParserFile; 3],
+    watch_interval: Duration,
+    update_interval: Duration,
+    /// Last seen modification time per parser file, indexed by `ParserKind::index`
+    last_modified: Mutex<[Option<SystemTime>; 3]>,
+}
+
+impl ParserUpdater {
+    pub fn new(config: Arc<Config>) -> Result<Self> {
+        let files = [
+            ParserFile {
+                kind: ParserKind::UserAgent,
+                path: config.ua_regexes_path.clone(),
+                url: config.ua_regexes_url.clone(),
+            },
+            ParserFile {
+                kind: ParserKind::Referrer,
+                path: config.referrer_db_path.clone(),
+                url: config.referrer_db_url.clone(),
+            },
+            ParserFile {
+                kind: ParserKind::ReferrerSupplement,
+                path: config.referrer_supplement_path.clone(),
+                url: None,
+            },
+        ];
+        let last_modified = files.each_ref().map(|file| modified_time(&file.path));
+
+        Ok(Self {
+            client: Client::builder().user_agent("betterlytics-updater/0.1").build()?,
+            files,
+            watch_interval: config.parser_watch_interval,
+            update_interval: config.parser_update_interval,
+            last_modified: Mutex::new(last_modified),
+        })
+    }
+
+    /// Polls the parser files and reloads any that changed since they were last loaded.
+    pub async fn run_watcher(self: Arc<Self>) {
+        info!("Watching parser data files for changes every {:?}", self.watch_interval);
+        let mut interval = interval(self.watch_interval);
+
+        loop {
+            interval.tick().await;
+            for file in &self.files {
+                let modified = modified_time(&file.path);
+                if modified.is_none() || modified == self.last_modified.lock().unwrap()[file.kind.index()] {
+                    continue;
+                }
+
+                info!("Detected change to {:?}, reloading {:?} parser", file.path, file.kind);
+                // Failures are logged and the previous parser stays active
+                let _ = self.reload_file(file);
+            }
+        }
+    }
+
+    /// Starts the background download loop. Does nothing when no URLs are configured.
+    pub async fn run_updater(self: Arc<Self>) {
+        if self.files.iter().all(|file| file.url.is_none()) {
+            info!("Parser data auto-update disabled (no UA_REGEXES_URL or REFERRER_DB_URL configured).");
+            return;
+        }
+
+        info!("Starting parser data update loop every {:?}", self.update_interval);
+        let mut interval = interval(self.update_interval);
+
+        loop {
+            interval.tick().await;
+            for file in &self.files {
+                let Some(url) = file.url.as_deref() else { continue };
+                debug!("Checking for {:?} parser data updates...", file.kind);
+                match self.download_and_replace(file, url).await {
+                    Ok(true) => info!("{:?} parser data updated from {}", file.kind, url),
+                    Ok(false) => debug!("{:?} parser data is up-to-date.", file.kind),
+                    Err(e) => error!("Failed to update {:?} parser data from {}: {:#}", file.kind, url, e),
+                }
+            }
+        }
+    }
+
+    /// Reloads all parser files from disk, e.g. on an admin trigger
+    pub fn reload_all(&self) -> Vec<ReloadResult> {
+        self.files
+            .iter()
+            .map(|file| {
+                let error = self.reload_file(file).err().map(|e| format!("{:#}", e));
+                ReloadResult {
+                    parser: file.kind,
+                    path: file.path.clone(),
+                    reloaded: error.is_none(),
+                    error,
+                }
+            })
+            .collect()
+    }
+
+    /// Reloads a single file. The modification time is recorded even on failure so that
+    /// a broken file is not retried on every poll; the previous parser stays active.
+    fn reload_file(&self, file: &ParserFile) -> Result<()> {
+        self.last_modified.lock().unwrap()[file.kind.index()] = modified_time(&file.path);
+
+        let result = file.kind.reload(&file.path);
+        if let Err(e) = &result {
+            error!("Keeping previous {:?} parser, failed to reload {:?}: {:#}", file.kind, file.path, e);
+        }
+        result
+    }
+
+    /// Downloads the file, validates it and atomically replaces the local copy.
+    /// Returns `false` if the downloaded file is identical to the local one.
+    async fn download_and_replace(&self, file: &ParserFile, url: &str) -> Result<bool> {
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            anyhow::bail!("Download request failed: {}", response.status());
+        }
+
+        let contents = response.bytes().await?;
+        if fs::read(&file.path).is_ok_and(|existing| existing == contents.as_ref()) {
+            return Ok(false);
+        }
+
+        if let Some(parent) = file.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut temp_path = file.path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+        fs::write(&temp_path, &contents).context("Failed to write temp parser data file")?;
+
+        if let Err(e) = file.kind.validate(&temp_path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+
+        fs::rename(&temp_path, &file.path).context("Failed to replace parser data file")?;
+        self.reload_file(file)?;
+        Ok(true)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
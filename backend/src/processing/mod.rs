@@ -1,14 +1,27 @@
 use anyhow::Result;
 use tokio::sync::mpsc;
-use tracing::{error, debug};
+use tracing::{error, debug, warn};
 use crate::analytics::{AnalyticsEvent, generate_fingerprint};
 use crate::geoip::GeoIpService;
-use crate::session;
+use crate::session::{self, SessionAttribution};
 use crate::bot_detection;
-use crate::referrer::{ReferrerInfo, parse_referrer};
+use crate::referrer::{self, ReferrerInfo, parse_referrer};
 use url::Url;
 use crate::campaign::{CampaignInfo, parse_campaign_params};
-use crate::ua_parser;
+use crate::channel::classify_channel;
+use crate::site_config::{self, SiteConfig};
+use crate::ua_parser::{self, ParsedUserAgent, UaDeviceClass};
+use crate::config::{Config, ReferrerSpamAction};
+use crate::cross_domain::CrossDomainToken;
+use crate::metrics::MetricsCollector;
+use crate::revenue::Revenue;
+use crate::normalization::UrlNormalizer;
+use crate::properties::{self, CustomEventProperties};
+use crate::schema::{SchemaMode, SchemaViolation};
+use moka::sync::Cache;
+use std::sync::Arc;
+
+const PAGE_TITLE_MAX_LENGTH: usize = 256;
 
 #[derive(Debug, Clone)]
 pub struct ProcessedEvent {
@@ -16,19 +29,43 @@ pub struct ProcessedEvent {
     pub event: AnalyticsEvent,
     /// Sessionization - new sessions are created if the user has not generated any events in over 30 minutes
     pub session_id: String,
-    /// Contains the domain of the URL (e.g. "example.com" or "subdomain.example.com")
+    /// First-touch referrer and campaign of the session, stamped on every event in it
+    pub session_attribution: SessionAttribution,
+    /// Contains the hostname of the URL (e.g. "example.com", "subdomain.example.com", "localhost" or "192.168.1.10")
     pub domain: Option<String>,
     /// Contains only the path of the URL (e.g. "/path/to/page" or "/")
     pub url: String,
+    /// Query parameters retained by the site's allowlist (e.g. "q=shoes&page=2"), empty by default
+    pub url_query: String,
+    /// Page title with control characters removed and whitespace collapsed, truncated to 256 characters
+    pub title: Option<String>,
     /// Geolocation data - Planning to use ip-api.com or maxmind to get this data
     pub country_code: Option<String>,
+    /// Region and city level geolocation - Only available when a City database is loaded
+    pub subdivision_code: Option<String>,
+    pub city: Option<String>,
+    pub timezone: Option<String>,
+    /// Autonomous system of the visitor's network - Only available when the ASN database is enabled
+    pub asn: Option<u32>,
+    pub asn_organization: Option<String>,
     /// Browser information - Parsed from user_agent string
     pub browser: Option<String>,
     pub browser_version: Option<String>,
+    /// Browser version with minor and patch components (e.g. "17.4")
+    pub browser_version_full: Option<String>,
+    /// Rendering engine (e.g. "Blink", "WebKit", "Gecko")
+    pub browser_engine: Option<String>,
     /// Operating system - Parsed from user_agent string
     pub os: Option<String>,
-    /// Device type (mobile, desktop, tablet) - Parsed from user_agent string
+    /// Operating system version (e.g. "17.4" or "11")
+    pub os_version: Option<String>,
+    /// Device type (mobile, tablet, laptop, desktop) - Combined from user agent device data and screen resolution
     pub device_type: Option<String>,
+    /// Device brand and model (e.g. "Apple" / "iPad") - Parsed from user_agent string
+    pub device_brand: Option<String>,
+    pub device_model: Option<String>,
+    /// App whose in-app browser sent the event (e.g. "Instagram") - Parsed from user_agent string
+    pub in_app_source: Option<String>,
     pub site_id: String,
     pub visitor_fingerprint: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
@@ -36,23 +73,44 @@ pub struct ProcessedEvent {
     pub referrer_info: ReferrerInfo,
     /// Parsed campaign parameters
     pub campaign_info: CampaignInfo,
+    /// Marketing channel (e.g. "Organic Search", "Paid Social") - Derived from referrer, campaign and click IDs
+    pub channel: String,
     pub user_agent: String,
     /// Custom event handling
     pub event_type: String,
     pub custom_event_name: String,
     pub custom_event_json: String,
+    /// Validated custom event properties, flattened to dotted keys
+    pub custom_event_properties: CustomEventProperties,
+    /// Ways in which a custom event does not conform to the site's event schema
+    pub schema_violations: Vec<SchemaViolation>,
+    /// Set in strict schema mode when there are violations - only the violations are stored, not the event
+    pub rejected_by_schema: bool,
+    /// Validated revenue of a purchase or other e-commerce event
+    pub revenue: Option<Revenue>,
 }
 
 /// Event processor that handles real-time processing
 pub struct EventProcessor {
     event_tx: mpsc::Sender<ProcessedEvent>,
     geoip_service: GeoIpService,
+    config: Arc<Config>,
+    metrics: Option<Arc<MetricsCollector>>,
+    /// Order IDs seen within the dedup window, keyed by site
+    order_ids: Cache<(String, String), ()>,
 }
 
 impl EventProcessor {
-    pub fn new(geoip_service: GeoIpService) -> (Self, mpsc::Receiver<ProcessedEvent>) {
+    pub fn new(
+        config: Arc<Config>,
+        geoip_service: GeoIpService,
+        metrics: Option<Arc<MetricsCollector>>,
+    ) -> (Self, mpsc::Receiver<ProcessedEvent>) {
         let (event_tx, event_rx) = mpsc::channel(100_000);
-        (Self { event_tx, geoip_service }, event_rx)
+        let order_ids = Cache::builder()
+            .time_to_live(config.order_dedup_window)
+            .build();
+        (Self { event_tx, geoip_service, config, metrics, order_ids }, event_rx)
     }
 
     pub async fn process_event(&self, event: AnalyticsEvent) -> Result<()> {
@@ -68,70 +126,153 @@ impl EventProcessor {
             return Ok(());
         }
 
-        let (domain, path) = self.extract_domain_and_path_from_url(&raw_url);
-        debug!("Extracted domain '{:?}' and path '{}' from URL '{}'", domain, path, raw_url);
+        if self.config.referrer_spam_action == ReferrerSpamAction::Drop
+            && let Some(referrer_url) = referrer.as_deref()
+            && referrer::is_spam_referrer(referrer_url)
+        {
+            debug!("Referrer spam detected, discarding event: {}", referrer_url);
+            return Ok(());
+        }
+
+        let site_config = site_config::get_site_config(&site_id);
+
+        let (domain, path, url_query) = self.extract_domain_and_path_from_url(&raw_url, &site_config.url_normalizer);
+        debug!("Extracted domain '{:?}', path '{}' and query '{}' from URL '{}'", domain, path, url_query, raw_url);
 
         let mut processed = ProcessedEvent {
             event: event.clone(),
             event_type: String::new(),
             session_id: String::new(),
+            session_attribution: SessionAttribution::default(),
             country_code: None,
+            subdivision_code: None,
+            city: None,
+            timezone: None,
+            asn: None,
+            asn_organization: None,
             browser: None,
             browser_version: None,
+            browser_version_full: None,
+            browser_engine: None,
             os: None,
+            os_version: None,
             device_type: None,
+            device_brand: None,
+            device_model: None,
+            in_app_source: None,
             site_id: site_id.clone(),
             visitor_fingerprint: String::new(),
             timestamp: timestamp.clone(),
             domain,
             url: path,
+            url_query,
+            title: event.raw.title.as_deref().and_then(sanitize_title),
             referrer_info: ReferrerInfo::default(),
             user_agent: user_agent.clone(),
             campaign_info: CampaignInfo::default(),
+            channel: String::new(),
             custom_event_name: String::new(),
             custom_event_json: String::new(),
+            custom_event_properties: CustomEventProperties::default(),
+            schema_violations: Vec::new(),
+            rejected_by_schema: false,
+            revenue: None,
         };
 
         // Handle event types
-        if let Err(e) = self.handle_event_types(&mut processed).await {
+        if let Err(e) = self.handle_event_types(&mut processed, &site_config).await {
             error!("Failed to handle event type: {}", e);
         }
 
-        // Parse referrer information
-        processed.referrer_info = parse_referrer(referrer.as_deref(), Some(&raw_url));
-        debug!("referrer_info: {:?}", processed.referrer_info);
-        
+        // Events rejected by the site's event schema skip further processing, only their violations are recorded
+        if processed.rejected_by_schema {
+            debug!("Custom event '{}' rejected by event schema: {:?}", processed.custom_event_name, processed.schema_violations);
+            if let Err(e) = self.event_tx.send(processed).await {
+                error!("Failed to send processed event: {}", e);
+            }
+            return Ok(());
+        }
+
+        if let Some(raw_revenue) = processed.event.raw.revenue.as_ref() {
+            let reporting_currency = site_config.reporting_currency.as_deref()
+                .unwrap_or(&self.config.default_reporting_currency);
+
+            match Revenue::from_raw(raw_revenue, reporting_currency) {
+                Ok(revenue) => {
+                    if let Some(order_id) = revenue.order_id.as_deref()
+                        && !self.is_first_order(&site_id, order_id)
+                    {
+                        debug!("Duplicate order '{}' for site {}, discarding event", order_id, site_id);
+                        self.record_revenue_result("duplicate_order");
+                        return Ok(());
+                    }
+                    self.record_revenue_result(if revenue.reporting_amount.is_some() { "accepted" } else { "unconverted" });
+                    processed.revenue = Some(revenue);
+                }
+                Err(e) => {
+                    debug!("Dropping invalid revenue of event '{}': {}", processed.event.raw.event_name, e);
+                    self.record_revenue_result(e.reason());
+                }
+            }
+        }
+
         // Parse campaign parameters from URL
-        processed.campaign_info = parse_campaign_params(&raw_url);
+        processed.campaign_info = parse_campaign_params(&raw_url, &site_config.campaign);
         debug!("campaign_info: {:?}", processed.campaign_info);
 
+        // Parse referrer information
+        processed.referrer_info = parse_referrer(
+            referrer.as_deref(),
+            Some(&raw_url),
+            processed.campaign_info.utm_source.as_deref(),
+            &site_config.internal_domains,
+        );
+        debug!("referrer_info: {:?}", processed.referrer_info);
+
         if let Err(e) = self.get_geolocation(&mut processed).await {
             error!("Failed to get geolocation: {}", e);
         }
 
-        if let Err(e) = self.detect_device_type_from_resolution(&mut processed).await {
-            error!("Failed to detect device type from resolution: {}", e);
-        }
-        
-        if let Err(e) = self.parse_user_agent(&mut processed).await {
-            error!("Failed to parse user agent: {}", e);
+        let ua_device_class = match self.parse_user_agent(&mut processed).await {
+            Ok(device_class) => device_class,
+            Err(e) => {
+                error!("Failed to parse user agent: {}", e);
+                None
+            }
+        };
+
+        if let Some(in_app_source) = processed.in_app_source.as_deref() {
+            referrer::apply_in_app_source(&mut processed.referrer_info, in_app_source);
+            debug!("In-app browser detected: {}, referrer_info: {:?}", in_app_source, processed.referrer_info);
         }
 
-        processed.visitor_fingerprint = generate_fingerprint(
-            &processed.event.ip_address,
-            processed.device_type.as_deref(),
-            processed.browser.as_deref(),
-            processed.browser_version.as_deref(),
-            processed.os.as_deref(),
+        processed.channel = classify_channel(
+            &processed.referrer_info,
+            &processed.campaign_info,
+            &raw_url,
+            site_config.channel_rules.as_ref(),
         );
+        debug!("channel: {}", processed.channel);
+
+        if let Err(e) = self.detect_device_type(&mut processed, ua_device_class).await {
+            error!("Failed to detect device type: {}", e);
+        }
 
-        let session_id_result = session::get_or_create_session_id(
+        processed.visitor_fingerprint = Self::visitor_fingerprint(&processed.event);
+        self.resolve_cross_domain_visitor(&mut processed);
+
+        let event_attribution = SessionAttribution::from_event(&processed.referrer_info, &processed.campaign_info);
+        let session_result = session::get_or_create_session(
             &site_id, 
             &processed.visitor_fingerprint, 
+            event_attribution,
         );
 
-        match session_id_result {
-            Ok(id) => processed.session_id = id,
+        match session_result {
+            Ok(session) => {
+                processed.session_id = session.session_id;
+                processed.session_attribution = session.attribution.unwrap_or_default();
+            }
             Err(e) => {
                 error!("Failed to get session ID: {}. Event processing aborted for: {:?}", e, processed.event);
                 return Ok(());
@@ -149,38 +290,75 @@ impl EventProcessor {
         Ok(())
     }
 
-    /// Extract domain and path from a URL string.
-    fn extract_domain_and_path_from_url(&self, url_str: &str) -> (Option<String>, String) {
+    /// Extract domain, normalized path and retained query string from a URL string.
+    fn extract_domain_and_path_from_url(&self, url_str: &str, normalizer: &UrlNormalizer) -> (Option<String>, String, String) {
         match Url::parse(url_str) {
             Ok(url) => {
-                let domain = url.domain().map(|d| d.to_string());
-                let path = if url.path().is_empty() {
-                    "/".to_string()
-                } else {
-                    url.path().to_string()
-                };
-                (domain, path)
+                // host_str also covers IP addresses, for which domain() is None
+                let domain = url.host_str().map(|host| host.to_string());
+                let (path, query) = normalizer.normalize_url(&url);
+                (domain, path, query)
             },
             Err(_) => {
                 // Since we get URLs from window.location.href, this should never happen
                 // But we'll try to handle it gracefully by treating the entire string as a path
                 debug!("Failed to parse URL '{}', treating as path-only", url_str);
-                if url_str.starts_with('/') {
-                    (None, url_str.to_string())
+                let path = if url_str.starts_with('/') {
+                    url_str.to_string()
                 } else {
-                    (None, format!("/{}", url_str))
-                }
+                    format!("/{}", url_str)
+                };
+                (None, normalizer.normalize_path(&path), String::new())
             }
         }
     }
 
+    /// Whether an order ID is seen for the first time within the dedup window.
+    /// This only avoids inserting obvious repeats, analytics.orders dedups durably across restarts and instances.
+    fn is_first_order(&self, site_id: &str, order_id: &str) -> bool {
+        self.order_ids
+            .entry((site_id.to_string(), order_id.to_string()))
+            .or_insert(())
+            .is_fresh()
+    }
+
+    fn record_revenue_result(&self, result: &str) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_revenue_event(result);
+        }
+    }
+
     /// Handle different event types
-    async fn handle_event_types(&self, processed: &mut ProcessedEvent) -> Result<()> {
+    async fn handle_event_types(&self, processed: &mut ProcessedEvent, site_config: &SiteConfig) -> Result<()> {
         let event_name = processed.event.raw.event_name.clone();
         if processed.event.raw.is_custom_event {
             processed.event_type = "custom".to_string();
             processed.custom_event_name = event_name;
-            processed.custom_event_json = processed.event.raw.properties.clone();
+
+            let (properties, rejections) = properties::parse_properties(&processed.event.raw.properties);
+            for rejection in &rejections {
+                debug!("Rejected custom event properties of '{}': {}", processed.custom_event_name, rejection.as_str());
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_property_rejection(rejection.as_str());
+                }
+            }
+
+            // The raw JSON is only kept when no property was rejected, so it never holds dropped values
+            if rejections.is_empty() {
+                processed.custom_event_json = processed.event.raw.properties.clone();
+            }
+            processed.custom_event_properties = properties;
+
+            if let Some(schema) = &site_config.event_schema {
+                let violations = schema.validate(&processed.custom_event_name, &processed.custom_event_properties);
+                if let Some(metrics) = &self.metrics {
+                    for violation in &violations {
+                        metrics.record_schema_violation(violation.kind.as_str(), schema.mode.action());
+                    }
+                }
+                processed.rejected_by_schema = schema.mode == SchemaMode::Strict && !violations.is_empty();
+                processed.schema_violations = violations;
+            }
         } else {
             processed.event_type = event_name;
         }
@@ -190,46 +368,258 @@ impl EventProcessor {
     /// Get geolocation data for the IP
     async fn get_geolocation(&self, processed: &mut ProcessedEvent) -> Result<()> {
         debug!("Getting geolocation data for IP: {}", processed.event.ip_address);
-        processed.country_code = self.geoip_service.lookup_country_code(&processed.event.ip_address);
-        if processed.country_code.is_some() {
-            debug!("Geolocation successful: {:?}", processed.country_code);
+        let location = self.geoip_service.lookup(processed.event.ip_address);
+        if location.country_code.is_some() {
+            debug!("Geolocation successful: {:?}", location);
         } else {
             debug!("Geolocation lookup returned no country code.");
         }
+        processed.country_code = location.country_code;
+        processed.subdivision_code = location.subdivision_code;
+        processed.city = location.city;
+        processed.timezone = location.timezone;
+
+        if self.config.enable_asn_lookup {
+            let asn_info = self.geoip_service
+                .lookup_asn(processed.event.ip_address)
+                .apply_privacy(self.config.asn_privacy);
+            debug!("ASN lookup result: {:?}", asn_info);
+            processed.asn = asn_info.number;
+            processed.asn_organization = asn_info.organization;
+        }
         Ok(())
     }
 
-    async fn parse_user_agent(&self, processed: &mut ProcessedEvent) -> Result<()> {
-        let parsed = ua_parser::parse_user_agent(&processed.user_agent);
+    /// Fingerprint built from the user agent string alone. Client hints are left out because the tracker only sends
+    /// high-entropy hints once they resolve and beacon or no-JS requests have none, which would split the visitor.
+    fn visitor_fingerprint(event: &AnalyticsEvent) -> String {
+        let parsed = ua_parser::parse_user_agent(&event.raw.user_agent);
+        let device_type = device_type_for(parsed.device_class, Some(&parsed.os), &event.raw.screen_resolution);
+        generate_fingerprint(
+            &event.ip_address,
+            Some(device_type),
+            Some(&parsed.browser),
+            parsed.browser_version.as_deref(),
+            Some(&parsed.os),
+        )
+    }
+
+    /// Parse the user agent, refined by any client hints sent with the event
+    fn parse_user_agent_with_hints(event: &AnalyticsEvent) -> ParsedUserAgent {
+        let mut parsed = ua_parser::parse_user_agent(&event.raw.user_agent);
+
+        // Client hints are not affected by user agent reduction, so they take priority over the UA string
+        if let Some(client_hints) = &event.raw.client_hints {
+            client_hints.apply(&mut parsed);
+            debug!("Applied client hints: {:?}, os_version={:?}", client_hints, client_hints.os_version());
+        }
+
+        parsed
+    }
+
+    async fn parse_user_agent(&self, processed: &mut ProcessedEvent) -> Result<Option<UaDeviceClass>> {
+        let parsed = Self::parse_user_agent_with_hints(&processed.event);
         
+        processed.os_version = parsed.os_version();
         processed.browser = Some(parsed.browser);
         processed.browser_version = parsed.browser_version;
+        processed.browser_version_full = parsed.browser_version_full;
+        processed.browser_engine = parsed.browser_engine;
         processed.os = Some(parsed.os);
+        processed.device_brand = parsed.device_brand;
+        processed.device_model = parsed.device_model;
+        processed.in_app_source = parsed.in_app_source;
         
         debug!(
-            "User agent parsed: browser={:?}, version={:?}, os={:?}, device_type={:?}",
-            processed.browser, processed.browser_version, processed.os, processed.device_type
+            "User agent parsed: browser={:?}, version={:?}, engine={:?}, os={:?}, os_version={:?}, device_family={:?}, device_brand={:?}, device_model={:?}",
+            processed.browser, processed.browser_version_full, processed.browser_engine, processed.os, processed.os_version,
+            parsed.device_family, processed.device_brand, processed.device_model
         );
         
+        Ok(parsed.device_class)
+    }
+
+    async fn detect_device_type(&self, processed: &mut ProcessedEvent, ua_device_class: Option<UaDeviceClass>) -> Result<()> {
+        let device_type = device_type_for(
+            ua_device_class,
+            processed.os.as_deref(),
+            &processed.event.raw.screen_resolution,
+        );
+        processed.device_type = Some(device_type.to_string());
         Ok(())
     }
-    
-    async fn detect_device_type_from_resolution(&self, processed: &mut ProcessedEvent) -> Result<()> {
-        if let Some((w, _h)) = processed.event.raw.screen_resolution.split_once('x') {
-            if let Ok(width) = w.trim().parse::<u32>() {
-                match width {
-                    0..=575 => processed.device_type = Some("mobile".to_string()),
-                    576..=991 => processed.device_type = Some("tablet".to_string()),
-                    992..=1439 => processed.device_type = Some("laptop".to_string()),
-                    _ => processed.device_type = Some("desktop".to_string()),
+
+    /// Follow a cross-domain token to the visitor and session it was issued for, and map
+    /// fingerprints previously linked that way back to their visitor
+    fn resolve_cross_domain_visitor(&self, processed: &mut ProcessedEvent) {
+        let Some(secret) = self.config.cross_domain_secret.as_deref() else {
+            return;
+        };
+
+        if let Some(token) = processed.event.raw.cross_domain_token.as_deref() {
+            match CrossDomainToken::verify(token, secret, chrono::Utc::now().timestamp() as u64) {
+                Ok(token) if token.site_id == processed.site_id => {
+                    debug!("Linking fingerprint {} to cross-domain visitor {}", processed.visitor_fingerprint, token.visitor_id);
+                    session::link_visitor(&processed.site_id, &processed.visitor_fingerprint, &token.visitor_id, &token.session_id);
                 }
-            } else {
-                processed.device_type = Some("unknown".to_string());
+                Ok(token) => warn!("Cross-domain token for site {} used on site {}", token.site_id, processed.site_id),
+                Err(e) => debug!("Rejected cross-domain token: {}", e),
             }
-        } else {
-            processed.device_type = Some("unknown".to_string());
         }
 
-        Ok(())
-    } 
-}
\ No newline at end of file
+        processed.visitor_fingerprint = session::resolve_visitor(&processed.site_id, &processed.visitor_fingerprint);
+    }
+
+    /// Issue a cross-domain token for the visitor and session that sent the event, with its lifetime in seconds.
+    /// Returns `None` when cross-domain tracking is disabled, the request comes from a bot or the visitor has no
+    /// active session. The session is only looked up, so requesting a token never starts or extends one.
+    pub fn issue_cross_domain_token(&self, event: &AnalyticsEvent) -> Result<Option<(String, u64)>> {
+        let Some(secret) = self.config.cross_domain_secret.as_deref() else {
+            return Ok(None);
+        };
+        if bot_detection::is_bot(&event.raw.user_agent) {
+            return Ok(None);
+        }
+
+        let fingerprint = Self::visitor_fingerprint(event);
+
+        let site_id = &event.raw.site_id;
+        let visitor_id = session::resolve_visitor(site_id, &fingerprint);
+        let Some(session) = session::find_session(site_id, &visitor_id) else {
+            debug!("No active session for visitor {} on site {}, not issuing a cross-domain token", visitor_id, site_id);
+            return Ok(None);
+        };
+
+        let token = CrossDomainToken {
+            site_id: site_id.clone(),
+            visitor_id,
+            session_id: session.session_id,
+            expires_at: chrono::Utc::now().timestamp() as u64 + self.config.cross_domain_token_ttl.as_secs(),
+        };
+        Ok(Some((token.sign(secret)?, self.config.cross_domain_token_ttl.as_secs())))
+    }
+}
+
+/// Strip control characters, collapse whitespace and truncate a page title
+fn sanitize_title(title: &str) -> Option<String> {
+    let title: String = title
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(PAGE_TITLE_MAX_LENGTH)
+        .collect();
+
+    (!title.is_empty()).then_some(title)
+}
+
+/// Decide the device type from the user agent's device class, refined by screen resolution.
+/// Falls back to resolution alone when the user agent gives no signal.
+fn device_type_for(ua_device_class: Option<UaDeviceClass>, os: Option<&str>, screen_resolution: &str) -> &'static str {
+    let resolution = screen_resolution.split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)));
+
+    match (ua_device_class, resolution) {
+        (Some(UaDeviceClass::Mobile), _) => "mobile",
+        (Some(UaDeviceClass::Tablet), _) => "tablet",
+        // iPadOS requests desktop sites with a Mac user agent, but Macs are never in portrait
+        (Some(UaDeviceClass::Desktop), Some((width, height)))
+            if os == Some("Mac OS X") && height > width => "tablet",
+        (Some(UaDeviceClass::Desktop), Some((width, _))) if width >= 1440 => "desktop",
+        (Some(UaDeviceClass::Desktop), _) => "laptop",
+        (None, Some((width, _))) => match width {
+            0..=575 => "mobile",
+            576..=991 => "tablet",
+            992..=1439 => "laptop",
+            _ => "desktop",
+        },
+        (None, None) => "unknown",
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::analytics::RawTrackingEvent;
+    use crate::ua_parser::ClientHints;
+
+    fn event(client_hints: Option<ClientHints>) -> AnalyticsEvent {
+        let raw = RawTrackingEvent {
+            site_id: "site".to_string(),
+            event_name: "pageview".to_string(),
+            is_custom_event: false,
+            properties: "{}".to_string(),
+            url: "https://example.com/".to_string(),
+            title: None,
+            referrer: None,
+            user_agent: "Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36".to_string(),
+            screen_resolution: "412x915".to_string(),
+            timestamp: 0,
+            client_hints,
+            revenue: None,
+            cross_domain_token: None,
+        };
+        AnalyticsEvent::new(raw, "203.0.113.7".parse().unwrap())
+    }
+
+    /// A processed custom event with all derived fields left empty
+    pub(crate) fn processed_event(custom_event_name: &str) -> ProcessedEvent {
+        let event = event(None);
+        ProcessedEvent {
+            event_type: "custom".to_string(),
+            session_id: "session".to_string(),
+            session_attribution: SessionAttribution::default(),
+            country_code: None,
+            subdivision_code: None,
+            city: None,
+            timezone: None,
+            asn: None,
+            asn_organization: None,
+            browser: None,
+            browser_version: None,
+            browser_version_full: None,
+            browser_engine: None,
+            os: None,
+            os_version: None,
+            device_type: None,
+            device_brand: None,
+            device_model: None,
+            in_app_source: None,
+            site_id: event.raw.site_id.clone(),
+            visitor_fingerprint: "visitor".to_string(),
+            timestamp: chrono::Utc::now(),
+            domain: Some("example.com".to_string()),
+            url: "/".to_string(),
+            url_query: String::new(),
+            title: None,
+            referrer_info: ReferrerInfo::default(),
+            user_agent: event.raw.user_agent.clone(),
+            campaign_info: CampaignInfo::default(),
+            channel: String::new(),
+            custom_event_name: custom_event_name.to_string(),
+            custom_event_json: String::new(),
+            custom_event_properties: CustomEventProperties::default(),
+            schema_violations: Vec::new(),
+            rejected_by_schema: false,
+            revenue: None,
+            event,
+        }
+    }
+
+    #[test]
+    fn fingerprint_ignores_client_hints() {
+        let hints = ClientHints {
+            brands: Vec::new(),
+            platform: Some("Android".to_string()),
+            platform_version: Some("14.0.0".to_string()),
+            mobile: Some(true),
+            model: Some("Pixel 8".to_string()),
+        };
+
+        assert_eq!(
+            EventProcessor::visitor_fingerprint(&event(Some(hints))),
+            EventProcessor::visitor_fingerprint(&event(None)),
+        );
+    }
+}
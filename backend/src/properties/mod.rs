@@ -0,0 +1,198 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Properties larger than this are not parsed at all
+const MAX_PROPERTIES_BYTES: usize = 32 * 1024;
+const MAX_PROPERTY_KEYS: usize = 50;
+const MAX_KEY_LENGTH: usize = 128;
+const MAX_VALUE_LENGTH: usize = 1024;
+/// Objects nested deeper than this are rejected, e.g. 3 allows "a.b.c"
+const MAX_NESTING_DEPTH: usize = 3;
+
+/// Why custom event properties (or a single property) were dropped, used as the metrics label
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyRejection {
+    /// The properties exceed the size limit - all properties are dropped
+    TooLarge,
+    /// The properties are not valid JSON - all properties are dropped
+    InvalidJson,
+    /// The properties are valid JSON but not an object - all properties are dropped
+    NotAnObject,
+    /// Objects are nested deeper than allowed - all properties are dropped
+    TooDeep,
+    /// More flattened keys than allowed - all properties are dropped
+    TooManyKeys,
+    /// A flattened key is too long - only that property is dropped
+    KeyTooLong,
+    /// A value is too long - only that property is dropped
+    ValueTooLong,
+    /// A flattened key has an empty segment (e.g. "" or "cart. ") - only that property is dropped
+    EmptyKey,
+    /// A flattened key was already used by another property (e.g. "a.b" and {"a": {"b": ..}}) - only the later property is dropped
+    DuplicateKey,
+}
+
+impl PropertyRejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropertyRejection::TooLarge => "too_large",
+            PropertyRejection::InvalidJson => "invalid_json",
+            PropertyRejection::NotAnObject => "not_an_object",
+            PropertyRejection::TooDeep => "too_deep",
+            PropertyRejection::TooManyKeys => "too_many_keys",
+            PropertyRejection::KeyTooLong => "key_too_long",
+            PropertyRejection::ValueTooLong => "value_too_long",
+            PropertyRejection::EmptyKey => "empty_key",
+            PropertyRejection::DuplicateKey => "duplicate_key",
+        }
+    }
+}
+
+/// JSON type of a flattened property value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyKind {
+    String,
+    Number,
+    Boolean,
+    Array,
+}
+
+impl PropertyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropertyKind::String => "string",
+            PropertyKind::Number => "number",
+            PropertyKind::Boolean => "boolean",
+            PropertyKind::Array => "array",
+        }
+    }
+}
+
+/// Custom event properties flattened to dotted keys (e.g. {"cart": {"items": 3}} becomes "cart.items")
+#[derive(Debug, Clone, Default)]
+pub struct CustomEventProperties {
+    /// Every property as a string, including numbers and booleans. Arrays are stored as JSON.
+    pub strings: Vec<(String, String)>,
+    /// Properties with number values
+    pub numbers: Vec<(String, f64)>,
+    kinds: HashMap<String, PropertyKind>,
+}
+
+impl CustomEventProperties {
+    /// JSON type of a property, if the event has it
+    pub fn kind_of(&self, key: &str) -> Option<PropertyKind> {
+        self.kinds.get(key).copied()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.strings.iter().map(|(key, _)| key.as_str())
+    }
+}
+
+/// Parse, validate and flatten the JSON properties sent with a custom event
+pub fn parse_properties(json: &str) -> (CustomEventProperties, Vec<PropertyRejection>) {
+    if json.trim().is_empty() {
+        return (CustomEventProperties::default(), Vec::new());
+    }
+    if json.len() > MAX_PROPERTIES_BYTES {
+        return (CustomEventProperties::default(), vec![PropertyRejection::TooLarge]);
+    }
+
+    let object = match serde_json::from_str::<Value>(json) {
+        Ok(Value::Object(object)) => object,
+        Ok(_) => return (CustomEventProperties::default(), vec![PropertyRejection::NotAnObject]),
+        Err(_) => return (CustomEventProperties::default(), vec![PropertyRejection::InvalidJson]),
+    };
+
+    let mut flattened = Vec::new();
+    if let Err(rejection) = flatten("", &object, 1, &mut flattened) {
+        return (CustomEventProperties::default(), vec![rejection]);
+    }
+    if flattened.len() > MAX_PROPERTY_KEYS {
+        return (CustomEventProperties::default(), vec![PropertyRejection::TooManyKeys]);
+    }
+
+    let mut properties = CustomEventProperties::default();
+    let mut rejections = Vec::new();
+    for (key, value) in flattened {
+        if key.split('.').any(str::is_empty) {
+            rejections.push(PropertyRejection::EmptyKey);
+            continue;
+        }
+        if properties.kinds.contains_key(&key) {
+            rejections.push(PropertyRejection::DuplicateKey);
+            continue;
+        }
+        if key.chars().count() > MAX_KEY_LENGTH {
+            rejections.push(PropertyRejection::KeyTooLong);
+            continue;
+        }
+
+        let string_value = match value {
+            Value::String(value) => value.clone(),
+            other => other.to_string(),
+        };
+        if string_value.chars().count() > MAX_VALUE_LENGTH {
+            rejections.push(PropertyRejection::ValueTooLong);
+            continue;
+        }
+
+        let kind = match value {
+            Value::Number(_) => PropertyKind::Number,
+            Value::Bool(_) => PropertyKind::Boolean,
+            Value::Array(_) => PropertyKind::Array,
+            _ => PropertyKind::String,
+        };
+        if let Some(number) = value.as_f64() {
+            properties.numbers.push((key.clone(), number));
+        }
+        properties.kinds.insert(key.clone(), kind);
+        properties.strings.push((key, string_value));
+    }
+
+    (properties, rejections)
+}
+
+/// Collect the leaves of an object under dotted keys. Null values are skipped.
+fn flatten<'a>(
+    prefix: &str,
+    object: &'a Map<String, Value>,
+    depth: usize,
+    out: &mut Vec<(String, &'a Value)>,
+) -> Result<(), PropertyRejection> {
+    if depth > MAX_NESTING_DEPTH {
+        return Err(PropertyRejection::TooDeep);
+    }
+
+    for (key, value) in object {
+        let key = if prefix.is_empty() { key.trim().to_string() } else { format!("{}.{}", prefix, key.trim()) };
+        match value {
+            Value::Null => {}
+            Value::Object(nested) => flatten(&key, nested, depth + 1, out)?,
+            _ => out.push((key, value)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_empty_and_duplicate_keys() {
+        let (properties, rejections) = parse_properties(r#"{"a.b": 1, "a": {"b": 2, " ": 3}, "": 4, "plan": "pro"}"#);
+        assert_eq!(properties.keys().collect::<Vec<_>>(), vec!["a.b", "plan"]);
+        assert_eq!(properties.numbers.len(), 1);
+        assert_eq!(rejections.iter().filter(|r| **r == PropertyRejection::EmptyKey).count(), 2);
+        assert_eq!(rejections.iter().filter(|r| **r == PropertyRejection::DuplicateKey).count(), 1);
+    }
+
+    #[test]
+    fn rejects_oversized_payload_before_parsing() {
+        let json = format!(r#"{{"value": "{}"}}"#, "x".repeat(MAX_PROPERTIES_BYTES));
+        let (properties, rejections) = parse_properties(&json);
+        assert_eq!(properties.keys().count(), 0);
+        assert_eq!(rejections, vec![PropertyRejection::TooLarge]);
+    }
+}
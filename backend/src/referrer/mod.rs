@@ -1,8 +1,17 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
 use refparser::RefDb;
-use std::sync::OnceLock;
+use std::sync::{Arc, RwLock};
 use url::Url;
 use std::path::Path;
-use tracing::info;
+use tracing::{info, error};
+
+mod spam;
+mod spam_updater;
+mod supplement;
+pub use spam::{initialize_spam_list, is_spam_host, is_spam_referrer};
+use supplement::{SupplementMatch, get_supplement, load_supplement, replace_supplement};
+pub use spam_updater::ReferrerSpamUpdater;
 
 /// Referrer source categories
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -12,6 +21,8 @@ pub enum ReferrerSource {
     Social,
     Email,
     Internal,
+    Spam,
+    AiAssistant,
     Other,
 }
 
@@ -30,6 +41,8 @@ impl ReferrerSource {
             ReferrerSource::Social => "social",
             ReferrerSource::Email => "email",
             ReferrerSource::Internal => "internal",
+            ReferrerSource::Spam => "spam",
+            ReferrerSource::AiAssistant => "ai",
             ReferrerSource::Other => "other",
         }
     }
@@ -41,6 +54,7 @@ impl ReferrerSource {
             Some("social") => ReferrerSource::Social,
             Some("internal") => ReferrerSource::Internal,
             Some("email") => ReferrerSource::Email,
+            Some("ai") => ReferrerSource::AiAssistant,
             None => ReferrerSource::Direct,
             _ => ReferrerSource::Other,
         }
@@ -60,24 +74,94 @@ pub struct ReferrerInfo {
     pub search_term: Option<String>,
 }
 
-static PARSER: OnceLock<RefDb> = OnceLock::new();
+/// Active referrer database, swapped atomically on reload
+static PARSER: Lazy<RwLock<Arc<RefDb>>> = Lazy::new(|| RwLock::new(Arc::new(RefDb::default())));
+
+/// A referrer that any usable referrer database must recognize
+const VALIDATION_REFERRER: &str = "https://www.google.com/search?q=betterlytics";
 
 pub fn initialize(referrer_db_path: &Path) {
     info!("Initializing referrer parser from: {:?}", referrer_db_path);
 
-    PARSER.get_or_init(|| {
-        match RefDb::from_json(referrer_db_path.to_str().unwrap_or("")) {
-            Ok(db) => db,
-            Err(e) => {
-                eprintln!("Warning: Could not load referer database from {:?}: {}. Using empty database.", referrer_db_path, e);
-                RefDb::default()
-            }
+    match load_referrer_db(referrer_db_path) {
+        Ok(db) => replace_referrer_db(db),
+        Err(e) => {
+            error!("Could not load referer database from {:?}: {:#}. Using empty database.", referrer_db_path, e);
+        }
+    }
+}
+
+/// Load a referrer database and check that it recognizes a well-known search engine
+pub fn load_referrer_db(referrer_db_path: &Path) -> Result<RefDb> {
+    let db = RefDb::from_json(referrer_db_path.to_str().unwrap_or(""))
+        .map_err(|e| anyhow::anyhow!("Failed to parse referrer database {:?}: {}", referrer_db_path, e))?;
+
+    let validation_url = Url::parse(VALIDATION_REFERRER)?;
+    match db.lookup(&validation_url) {
+        Some(info) if info.medium == "search" => Ok(db),
+        _ => anyhow::bail!("Referrer database {:?} failed validation: {} is not recognized as a search engine", referrer_db_path, VALIDATION_REFERRER),
+    }
+}
+
+pub fn replace_referrer_db(db: RefDb) {
+    *PARSER.write().unwrap() = Arc::new(db);
+}
+
+/// Load, validate and swap in the referrer database at the given path
+pub fn reload(referrer_db_path: &Path) -> Result<()> {
+    let db = load_referrer_db(referrer_db_path)?;
+    replace_referrer_db(db);
+    info!("Referrer database reloaded from {:?}", referrer_db_path);
+    Ok(())
+}
+
+/// Load the supplementary referrer rules that are checked before the referrer database
+pub fn initialize_supplement(supplement_path: &Path) {
+    info!("Initializing referrer supplement from: {:?}", supplement_path);
+
+    if let Err(e) = reload_supplement(supplement_path) {
+        error!("Could not load referrer supplement from {:?}: {:#}. Only the referrer database will be used.", supplement_path, e);
+    }
+}
+
+/// Load and swap in the supplementary referrer rules at the given path
+pub fn reload_supplement(supplement_path: &Path) -> Result<()> {
+    let supplement = load_supplement(supplement_path)?;
+    info!("Loaded {} supplementary referrer domains from {:?}", supplement.len(), supplement_path);
+    replace_supplement(supplement);
+    Ok(())
+}
+
+/// Validate the supplementary referrer rules at the given path without swapping them in
+pub fn validate_supplement(supplement_path: &Path) -> Result<()> {
+    let supplement = load_supplement(supplement_path)?;
+    if supplement.is_empty() {
+        anyhow::bail!("Referrer supplement {:?} contains no domains", supplement_path);
+    }
+    Ok(())
+}
+
+fn get_parser() -> Arc<RefDb> {
+    PARSER.read().unwrap().clone()
+}
+
+impl ReferrerInfo {
+    fn from_supplement(url: Option<String>, entry: SupplementMatch) -> Self {
+        ReferrerInfo {
+            url,
+            source_type: ReferrerSource::from_referrer_medium(Some(&entry.medium)),
+            source_name: Some(entry.source_name),
+            search_term: None,
         }
-    });
+    }
 }
 
-fn get_parser() -> &'static RefDb {
-    PARSER.get().expect("Referrer parser not initialized. Call initialize() first.")
+/// In-app browsers usually strip the referrer, so attribute referrer-less visits to the app they came from
+pub fn apply_in_app_source(referrer_info: &mut ReferrerInfo, in_app_source: &str) {
+    if referrer_info.source_type == ReferrerSource::Direct {
+        referrer_info.source_type = ReferrerSource::Social;
+        referrer_info.source_name = Some(in_app_source.to_string());
+    }
 }
 
 /// Sanitize a referrer URL for privacy compliance
@@ -115,22 +199,31 @@ fn sanitize_referrer_url(referrer_url: &Url, is_search_engine: bool, search_para
     }
 }
 
-/// Parse a referrer URL and extract useful information
-pub fn parse_referrer(referrer: Option<&str>, current_url: Option<&str>) -> ReferrerInfo {
-    let current_host = current_url.and_then(|url| {
-        Url::parse(url).ok().and_then(|u| u.host_str().map(|h| h.to_string()))
-    });
+/// Parse a referrer URL and extract useful information.
+/// `utm_source` is the campaign source parsed from the current URL, including aliases and fragment parameters.
+/// `internal_domains` lists additional domains that belong to the same property as the current URL.
+pub fn parse_referrer(referrer: Option<&str>, current_url: Option<&str>, utm_source: Option<&str>, internal_domains: &[String]) -> ReferrerInfo {
+    let current_url = current_url.and_then(|url| Url::parse(url).ok());
+    let current_host = current_url.as_ref().and_then(|u| u.host_str().map(|h| h.to_string()));
+    let supplement = get_supplement();
+
+    // Some sources (e.g. ChatGPT) strip the referrer but tag links with utm_source
+    let utm_source_match = utm_source
+        .and_then(|utm_source| supplement.lookup_utm_source(utm_source).cloned());
     
     // If no referrer, it's a direct visit
     let referrer_str = match referrer {
         Some(r) if !r.is_empty() => r,
         _ => {
-            return ReferrerInfo {
-                url: None,
-                source_type: ReferrerSource::Direct,
-                source_name: None,
-                search_term: None,
-            }
+            return match utm_source_match {
+                Some(entry) => ReferrerInfo::from_supplement(None, entry),
+                None => ReferrerInfo {
+                    url: None,
+                    source_type: ReferrerSource::Direct,
+                    source_name: None,
+                    search_term: None,
+                },
+            };
         }
     };
 
@@ -146,6 +239,35 @@ pub fn parse_referrer(referrer: Option<&str>, current_url: Option<&str>) -> Refe
         }
     };
     
+    // Flag known referrer spam before consulting the referrer database
+    if let Some(ref_host) = referrer_url.host_str()
+        && is_spam_host(ref_host)
+    {
+        return ReferrerInfo {
+            url: Some(sanitize_referrer_url(&referrer_url, false, &[])),
+            source_type: ReferrerSource::Spam,
+            source_name: Some(ref_host.to_string()),
+            search_term: None,
+        };
+    }
+
+    // Navigation within the same property is internal, whatever the referrer database says about the domain
+    if let (Some(ref_host), Some(curr_host)) = (referrer_url.host_str(), current_host.as_deref())
+        && is_internal_referrer(ref_host, curr_host, internal_domains)
+    {
+        return ReferrerInfo {
+            url: Some(sanitize_referrer_url(&referrer_url, false, &[])),
+            source_type: ReferrerSource::Internal,
+            source_name: None,
+            search_term: None,
+        };
+    }
+
+    // Supplementary rules take precedence over the referrer database
+    if let Some(entry) = referrer_url.host_str().and_then(|host| supplement.lookup_host(host)) {
+        return ReferrerInfo::from_supplement(Some(sanitize_referrer_url(&referrer_url, false, &[])), entry.clone());
+    }
+
     // Get the parser and lookup the referrer URL
     let parser = get_parser();
     let referrer_info = parser.lookup(&referrer_url);
@@ -160,25 +282,9 @@ pub fn parse_referrer(referrer: Option<&str>, current_url: Option<&str>) -> Refe
 
         ReferrerSource::from_referrer_medium(medium)
     } else {
-        // Fallback when the referer-parser doesn't recognize the URL
-        // This handles cases where:
-        // 1. The referrer is from a domain not in the referrer database
-        // 2. Navigation between subdomains of the same site
-        // 3. Custom or internal domains within an organization
-        // 4. Users who have explicitly set referrer policies to preserve internal referrers
-        if let Some(ref_host) = referrer_url.host_str() {
-            if let Some(curr_host) = current_host {
-                if is_internal_referrer(ref_host, &curr_host) {
-                    ReferrerSource::Internal
-                } else {
-                    ReferrerSource::Other
-                }
-            } else {
-                ReferrerSource::Other
-            }
-        } else {
-            ReferrerSource::Other
-        }
+        // Fallback when the referer-parser doesn't recognize the URL.
+        // Internal referrers were already handled above, so this is a domain outside the referrer database.
+        ReferrerSource::Other
     };
     
     // Extract search term if available
@@ -201,6 +307,13 @@ pub fn parse_referrer(referrer: Option<&str>, current_url: Option<&str>) -> Refe
         (false, &[] as &[String])
     };
 
+    // An unrecognized referrer combined with a known utm_source is attributed to that source
+    if source_type == ReferrerSource::Other
+        && let Some(entry) = utm_source_match
+    {
+        return ReferrerInfo::from_supplement(Some(sanitize_referrer_url(&referrer_url, false, &[])), entry);
+    }
+
     // Sanitize the referrer URL to ensure privacy compliance to ensure that the referrer url does not contain any sensitive information
     let sanitized_referrer = sanitize_referrer_url(&referrer_url, is_search_engine, search_params);
 
@@ -212,9 +325,34 @@ pub fn parse_referrer(referrer: Option<&str>, current_url: Option<&str>) -> Refe
     }
 }
 
-/// Check if the referrer is internal to the current host
-fn is_internal_referrer(referrer_host: &str, current_host: &String) -> bool {
-    referrer_host == current_host.as_str()
+/// Check if the referrer belongs to the same property as the current host: the same registrable domain
+/// (e.g. "www.example.com" and "shop.example.com"), or one of the site's configured internal domains
+fn is_internal_referrer(referrer_host: &str, current_host: &str, internal_domains: &[String]) -> bool {
+    let referrer_host = referrer_host.to_lowercase();
+    let current_host = current_host.to_lowercase();
+
+    if referrer_host == current_host {
+        return true;
+    }
+
+    // IP addresses and single-label hosts like "localhost" have no registrable domain
+    if let (Some(referrer_domain), Some(current_domain)) = (registrable_domain(&referrer_host), registrable_domain(&current_host))
+        && referrer_domain == current_domain
+    {
+        return true;
+    }
+
+    internal_domains.iter().any(|domain| {
+        let domain = domain.to_lowercase();
+        referrer_host == domain || referrer_host.ends_with(&format!(".{}", domain))
+    })
+}
+
+fn registrable_domain(host: &str) -> Option<&str> {
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return None;
+    }
+    psl::domain_str(host)
 }
 
 /// Extract search term from URL using parameter names
@@ -229,4 +367,33 @@ fn extract_search_term(url: &Url, param_names: &[String]) -> Option<String> {
         }
     }
     None
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registrable_domain_uses_public_suffixes() {
+        assert_eq!(registrable_domain("shop.example.co.uk"), Some("example.co.uk"));
+        assert_eq!(registrable_domain("www.example.com"), Some("example.com"));
+        assert_eq!(registrable_domain("127.0.0.1"), None);
+        assert_eq!(registrable_domain("[::1]"), None);
+    }
+
+    #[test]
+    fn subdomains_of_the_same_site_are_internal() {
+        assert!(is_internal_referrer("www.example.co.uk", "shop.example.co.uk", &[]));
+        assert!(is_internal_referrer("WWW.Example.com", "example.com", &[]));
+        assert!(!is_internal_referrer("other.co.uk", "example.co.uk", &[]));
+        assert!(!is_internal_referrer("127.0.0.1", "127.0.0.2", &[]));
+    }
+
+    #[test]
+    fn internal_domains_include_their_subdomains() {
+        let internal_domains = vec!["checkout-provider.com".to_string()];
+        assert!(is_internal_referrer("pay.checkout-provider.com", "example.com", &internal_domains));
+        assert!(is_internal_referrer("checkout-provider.com", "example.com", &internal_domains));
+        assert!(!is_internal_referrer("notcheckout-provider.com", "example.com", &internal_domains));
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use once_cell::sync::Lazy;
+use anyhow::{Result, Context};
+use url::Url;
+use tracing::{info, warn};
+
+/// Referrer spam domain list.
+/// Plain entries (e.g. "semalt.com") match the domain itself and all of its subdomains,
+/// entries prefixed with "*." (e.g. "*.semalt.com") only match subdomains.
+#[derive(Debug, Default)]
+pub struct SpamList {
+    domains: HashSet<String>,
+    subdomain_only: HashSet<String>,
+}
+
+impl SpamList {
+    /// Parse a newline separated domain list, ignoring blank lines and `#` comments
+    pub fn parse(contents: &str) -> Self {
+        let mut list = SpamList::default();
+
+        for line in contents.lines() {
+            let entry = line.split('#').next().unwrap_or("").trim().trim_end_matches('.');
+            if entry.is_empty() {
+                continue;
+            }
+
+            let entry = entry.to_lowercase();
+            match entry.strip_prefix("*.") {
+                Some(parent) => { list.subdomain_only.insert(parent.to_string()); }
+                None => { list.domains.insert(entry); }
+            }
+        }
+
+        list
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len() + self.subdomain_only.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check whether a host or any of its parent domains is on the list
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_lowercase();
+        if self.domains.contains(&host) {
+            return true;
+        }
+
+        let mut rest = host.as_str();
+        while let Some((_, parent)) = rest.split_once('.') {
+            if self.domains.contains(parent) || self.subdomain_only.contains(parent) {
+                return true;
+            }
+            rest = parent;
+        }
+        false
+    }
+}
+
+static SPAM_LIST: Lazy<RwLock<Arc<SpamList>>> = Lazy::new(|| RwLock::new(Arc::new(SpamList::default())));
+
+pub fn initialize_spam_list(spam_list_path: &Path) {
+    info!("Initializing referrer spam list from: {:?}", spam_list_path);
+
+    match load_spam_list(spam_list_path) {
+        Ok(list) => {
+            info!("Loaded {} referrer spam domains", list.len());
+            replace_spam_list(list);
+        }
+        Err(e) => {
+            warn!("Could not load referrer spam list from {:?}: {}. Spam filtering disabled until next update.", spam_list_path, e);
+        }
+    }
+}
+
+/// Read and parse a spam list file from disk
+pub fn load_spam_list(spam_list_path: &Path) -> Result<SpamList> {
+    let contents = fs::read_to_string(spam_list_path)
+        .with_context(|| format!("Failed to read referrer spam list {:?}", spam_list_path))?;
+    Ok(SpamList::parse(&contents))
+}
+
+/// Atomically swap the active spam list
+pub fn replace_spam_list(list: SpamList) {
+    *SPAM_LIST.write().unwrap() = Arc::new(list);
+}
+
+fn get_spam_list() -> Arc<SpamList> {
+    SPAM_LIST.read().unwrap().clone()
+}
+
+/// Check if a referrer host is a known spam domain
+pub fn is_spam_host(host: &str) -> bool {
+    get_spam_list().matches(host)
+}
+
+/// Check if a raw referrer URL points to a known spam domain
+pub fn is_spam_referrer(referrer: &str) -> bool {
+    Url::parse(referrer)
+        .ok()
+        .and_then(|url| url.host_str().map(is_spam_host))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_listed_domains_and_their_subdomains() {
+        let list = SpamList::parse("spam.com\n");
+        assert!(list.matches("spam.com"));
+        assert!(list.matches("www.spam.com"));
+        assert!(list.matches("a.b.spam.com"));
+        assert!(!list.matches("notspam.com"));
+        assert!(!list.matches("spam.com.example.org"));
+    }
+
+    #[test]
+    fn wildcard_entries_only_match_subdomains() {
+        let list = SpamList::parse("*.wild.net\n");
+        assert!(list.matches("x.wild.net"));
+        assert!(list.matches("y.x.wild.net"));
+        assert!(!list.matches("wild.net"));
+    }
+
+    #[test]
+    fn matching_ignores_case_trailing_dots_and_comments() {
+        let list = SpamList::parse("# comment\n\n  Spam.COM.  # trailing comment\n");
+        assert_eq!(list.len(), 1);
+        assert!(list.matches("WWW.spam.com."));
+    }
+}
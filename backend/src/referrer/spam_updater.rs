@@ -0,0 +1,109 @@
+use crate::config::Config;
+use super::spam::{SpamList, replace_spam_list};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{info, error, debug};
+use anyhow::{Result, Context};
+use reqwest::{Client, StatusCode, header};
+use httpdate::fmt_http_date;
+
+pub struct ReferrerSpamUpdater {
+    client: Client,
+    list_url: Option<String>,
+    list_path: PathBuf,
+    update_interval: Duration,
+}
+
+impl ReferrerSpamUpdater {
+    pub fn new(config: Arc<Config>) -> Result<Self> {
+        Ok(Self {
+            client: Client::builder().user_agent("betterlytics-updater/0.1").build()?,
+            list_url: config.referrer_spam_list_url.clone(),
+            list_path: config.referrer_spam_list_path.clone(),
+            update_interval: config.referrer_spam_update_interval,
+        })
+    }
+
+    /// Starts the background update check loop.
+    pub async fn run(self: Arc<Self>) {
+        let Some(list_url) = self.list_url.as_deref() else {
+            info!("Referrer spam list auto-update disabled (no REFERRER_SPAM_LIST_URL configured).");
+            return;
+        };
+
+        info!("Starting referrer spam list update loop for {} every {:?}", list_url, self.update_interval);
+        let mut interval = interval(self.update_interval);
+
+        loop {
+            interval.tick().await;
+            self.check_and_update(list_url).await;
+        }
+    }
+
+    async fn check_and_update(&self, list_url: &str) {
+        debug!("Checking for referrer spam list updates...");
+        match self.download_if_modified(list_url).await {
+            Ok(Some(contents)) => {
+                match self.validate_and_replace(&contents) {
+                    Ok(count) => info!("Referrer spam list updated successfully ({} domains).", count),
+                    Err(e) => error!("Failed to apply referrer spam list update: {}", e),
+                }
+            }
+            Ok(None) => {
+                debug!("Local referrer spam list is up-to-date.");
+            }
+            Err(e) => {
+                error!("Failed to download referrer spam list: {}", e);
+            }
+        }
+    }
+
+    /// Downloads the list, using the local file modification time for a conditional request.
+    /// Returns `None` if the remote list has not changed.
+    async fn download_if_modified(&self, list_url: &str) -> Result<Option<String>> {
+        let mut request = self.client.get(list_url);
+
+        if let Ok(modified) = fs::metadata(&self.list_path).and_then(|m| m.modified()) {
+            request = request.header(header::IF_MODIFIED_SINCE, fmt_http_date(modified));
+        }
+
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            anyhow::bail!("Download request failed: {}", response.status());
+        }
+
+        let contents = response.text().await?;
+        match fs::read_to_string(&self.list_path) {
+            Ok(existing) if existing == contents => Ok(None),
+            _ => Ok(Some(contents)),
+        }
+    }
+
+    /// Parses the downloaded list, then writes it to a temp file, atomically renames it and swaps the active list.
+    fn validate_and_replace(&self, contents: &str) -> Result<usize> {
+        let list = SpamList::parse(contents);
+        if list.is_empty() {
+            anyhow::bail!("Downloaded referrer spam list contains no domains");
+        }
+
+        if let Some(parent) = self.list_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = self.list_path.with_extension("txt.tmp");
+        fs::write(&temp_path, contents).context("Failed to write temp referrer spam list")?;
+        fs::rename(&temp_path, &self.list_path).context("Failed to replace referrer spam list")?;
+
+        let count = list.len();
+        replace_spam_list(list);
+        Ok(count)
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{Result, Context};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// A source entry in the snowplow referrer format, extended with `utm_sources`
+#[derive(Debug, Deserialize)]
+struct SupplementSource {
+    #[serde(default)]
+    domains: Vec<String>,
+    /// utm_source values that identify this source when the referrer is stripped
+    #[serde(default)]
+    utm_sources: Vec<String>,
+}
+
+/// Medium and source name of a supplementary referrer entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupplementMatch {
+    pub medium: String,
+    pub source_name: String,
+}
+
+/// Referrer rules that take precedence over the snowplow database, e.g. for AI assistants
+/// that the upstream database does not know about yet
+#[derive(Debug, Default)]
+pub struct ReferrerSupplement {
+    domains: HashMap<String, SupplementMatch>,
+    utm_sources: HashMap<String, SupplementMatch>,
+}
+
+impl ReferrerSupplement {
+    /// Parse a file in the snowplow format: `{ "<medium>": { "<source name>": { "domains": [...], "utm_sources": [...] } } }`
+    pub fn parse(contents: &str) -> Result<Self> {
+        let file: HashMap<String, HashMap<String, SupplementSource>> = serde_json::from_str(contents)?;
+        let mut supplement = ReferrerSupplement::default();
+
+        for (medium, sources) in file {
+            for (source_name, source) in sources {
+                let entry = SupplementMatch { medium: medium.clone(), source_name };
+                for domain in source.domains {
+                    supplement.domains.insert(domain.to_lowercase(), entry.clone());
+                }
+                for utm_source in source.utm_sources {
+                    supplement.utm_sources.insert(utm_source.to_lowercase(), entry.clone());
+                }
+            }
+        }
+
+        Ok(supplement)
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    /// Look up a host, walking up to parent domains (e.g. "www.perplexity.ai" matches "perplexity.ai")
+    pub fn lookup_host(&self, host: &str) -> Option<&SupplementMatch> {
+        let host = host.to_lowercase();
+        let mut candidate = host.as_str();
+        loop {
+            if let Some(entry) = self.domains.get(candidate) {
+                return Some(entry);
+            }
+            candidate = candidate.split_once('.')?.1;
+        }
+    }
+
+    pub fn lookup_utm_source(&self, utm_source: &str) -> Option<&SupplementMatch> {
+        self.utm_sources.get(&utm_source.to_lowercase())
+    }
+}
+
+static SUPPLEMENT: Lazy<RwLock<Arc<ReferrerSupplement>>> = Lazy::new(|| RwLock::new(Arc::new(ReferrerSupplement::default())));
+
+pub fn load_supplement(supplement_path: &Path) -> Result<ReferrerSupplement> {
+    let contents = fs::read_to_string(supplement_path)
+        .with_context(|| format!("Failed to read referrer supplement {:?}", supplement_path))?;
+    ReferrerSupplement::parse(&contents)
+        .with_context(|| format!("Failed to parse referrer supplement {:?}", supplement_path))
+}
+
+pub fn replace_supplement(supplement: ReferrerSupplement) {
+    *SUPPLEMENT.write().unwrap() = Arc::new(supplement);
+}
+
+pub fn get_supplement() -> Arc<ReferrerSupplement> {
+    SUPPLEMENT.read().unwrap().clone()
+}
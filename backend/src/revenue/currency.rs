@@ -0,0 +1,141 @@
+use anyhow::{Result, Context};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Active ISO 4217 currency codes
+const ISO_CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN", "BHD",
+    "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CLP", "CNY",
+    "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP",
+    "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT",
+    "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR",
+    "MVR", "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK",
+    "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD",
+    "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT", "TND", "TOP", "TRY",
+    "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VES", "VND", "VUV", "WST", "XAF", "XCD", "XCG",
+    "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWG",
+];
+
+/// Normalize a currency code to upper case, returning `None` if it is not an ISO 4217 code
+pub fn normalize_currency(currency: &str) -> Option<String> {
+    let currency = currency.trim().to_ascii_uppercase();
+    ISO_CURRENCIES.contains(&currency.as_str()).then_some(currency)
+}
+
+/// Exchange rates relative to a base currency, as written in the exchange rates file:
+/// `{ "base": "EUR", "rates": { "USD": 1.08, "GBP": 0.85 } }`, where one unit of the base currency is worth `rate` units
+#[derive(Debug, Default, Deserialize)]
+pub struct ExchangeRates {
+    pub base: Option<String>,
+    #[serde(default)]
+    rates: HashMap<String, f64>,
+}
+
+impl ExchangeRates {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut exchange_rates: ExchangeRates = serde_json::from_str(contents)?;
+
+        let base = exchange_rates.base.as_deref()
+            .map(|base| normalize_currency(base).with_context(|| format!("Unknown base currency '{}'", base)))
+            .transpose()?;
+
+        let mut rates = HashMap::with_capacity(exchange_rates.rates.len() + 1);
+        for (currency, rate) in exchange_rates.rates {
+            let code = normalize_currency(&currency).with_context(|| format!("Unknown currency '{}'", currency))?;
+            anyhow::ensure!(rate.is_finite() && rate > 0.0, "Invalid exchange rate {} for {}", rate, code);
+            rates.insert(code, rate);
+        }
+        if let Some(base) = &base {
+            rates.insert(base.clone(), 1.0);
+        }
+
+        exchange_rates.base = base;
+        exchange_rates.rates = rates;
+        Ok(exchange_rates)
+    }
+
+    pub fn len(&self) -> usize {
+        self.rates.len()
+    }
+
+    /// Convert an amount between currencies, `None` if either currency has no rate
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Option<f64> {
+        if from == to {
+            return Some(amount);
+        }
+        let from_rate = self.rates.get(from)?;
+        let to_rate = self.rates.get(to)?;
+        Some(amount / from_rate * to_rate)
+    }
+}
+
+static EXCHANGE_RATES: Lazy<RwLock<Arc<ExchangeRates>>> = Lazy::new(|| RwLock::new(Arc::new(ExchangeRates::default())));
+
+pub fn load_exchange_rates(exchange_rates_path: &Path) -> Result<ExchangeRates> {
+    let contents = fs::read_to_string(exchange_rates_path)
+        .with_context(|| format!("Failed to read exchange rates {:?}", exchange_rates_path))?;
+    ExchangeRates::parse(&contents)
+        .with_context(|| format!("Failed to parse exchange rates {:?}", exchange_rates_path))
+}
+
+pub fn replace_exchange_rates(exchange_rates: ExchangeRates) {
+    *EXCHANGE_RATES.write().unwrap() = Arc::new(exchange_rates);
+}
+
+pub fn get_exchange_rates() -> Arc<ExchangeRates> {
+    EXCHANGE_RATES.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rates_and_adds_the_base_currency() {
+        let exchange_rates = ExchangeRates::parse(r#"{"base": "eur", "rates": {"usd": 1.08, "GBP": 0.85}}"#).unwrap();
+
+        assert_eq!(exchange_rates.base.as_deref(), Some("EUR"));
+        assert_eq!(exchange_rates.len(), 3);
+        assert_eq!(exchange_rates.convert(100.0, "EUR", "USD"), Some(108.0));
+    }
+
+    #[test]
+    fn rejects_invalid_rates() {
+        for contents in [
+            r#"{"base": "EUR", "rates": {"USD": 0}}"#,
+            r#"{"base": "EUR", "rates": {"USD": -1.08}}"#,
+            r#"{"base": "EUR", "rates": {"USD": null}}"#,
+            r#"{"base": "EUR", "rates": {"USD": "1.08"}}"#,
+            r#"{"base": "EUR", "rates": {"XYZ": 1.5}}"#,
+            r#"{"base": "XYZ", "rates": {"USD": 1.08}}"#,
+        ] {
+            assert!(ExchangeRates::parse(contents).is_err(), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn converts_between_non_base_currencies() {
+        let exchange_rates = ExchangeRates::parse(r#"{"base": "EUR", "rates": {"USD": 1.25, "GBP": 0.8}}"#).unwrap();
+
+        let converted = exchange_rates.convert(125.0, "USD", "GBP").unwrap();
+        assert!((converted - 80.0).abs() < 1e-9, "{}", converted);
+        let converted = exchange_rates.convert(80.0, "GBP", "USD").unwrap();
+        assert!((converted - 125.0).abs() < 1e-9, "{}", converted);
+    }
+
+    #[test]
+    fn does_not_convert_unknown_currencies() {
+        let exchange_rates = ExchangeRates::parse(r#"{"base": "EUR", "rates": {"USD": 1.08}}"#).unwrap();
+
+        assert_eq!(exchange_rates.convert(10.0, "JPY", "EUR"), None);
+        assert_eq!(exchange_rates.convert(10.0, "EUR", "JPY"), None);
+        // Identical currencies need no rate
+        assert_eq!(exchange_rates.convert(10.0, "JPY", "JPY"), Some(10.0));
+        assert_eq!(ExchangeRates::default().convert(10.0, "EUR", "USD"), None);
+    }
+}
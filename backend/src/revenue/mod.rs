@@ -0,0 +1,214 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{info, error};
+
+mod currency;
+pub use currency::normalize_currency;
+use currency::{get_exchange_rates, load_exchange_rates, replace_exchange_rates};
+
+/// Largest accepted amount, small enough that the amount scaled by 10^4 is still an exact integer in an f64
+const MAX_AMOUNT: f64 = 100_000_000_000.0;
+const MAX_ORDER_ID_LENGTH: usize = 128;
+const MAX_ITEMS: usize = 100;
+const MAX_ITEM_FIELD_LENGTH: usize = 256;
+/// Amounts are stored as Decimal(18, 4), i.e. integers scaled by 10^4
+const DECIMAL_SCALE: f64 = 10_000.0;
+
+/// Revenue sent with a purchase or other e-commerce event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RawRevenue {
+    pub amount: RawAmount,
+    /// ISO 4217 currency code, e.g. "EUR"
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Used to drop repeated events for the same purchase, e.g. from reloading a confirmation page
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default)]
+    pub items: Vec<RevenueItem>,
+}
+
+/// Amounts are accepted as numbers or numeric strings, e.g. "59.90"
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RawAmount {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevenueItem {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Defaults to 1
+    #[serde(default)]
+    pub quantity: Option<f64>,
+    /// Unit price in the currency of the revenue
+    #[serde(default)]
+    pub price: Option<f64>,
+}
+
+/// Why the revenue of an event was dropped, used as the metrics label
+#[derive(Debug, thiserror::Error)]
+pub enum RevenueError {
+    #[error("revenue is not an object with an amount, currency and valid optional fields")]
+    Malformed,
+    #[error("amount {0} is not a non-negative number below the maximum")]
+    InvalidAmount(String),
+    #[error("currency '{0}' is not an ISO 4217 code")]
+    InvalidCurrency(String),
+    #[error("order ID is empty or longer than {MAX_ORDER_ID_LENGTH} characters")]
+    InvalidOrderId,
+    #[error("item list is too long or contains an invalid item")]
+    InvalidItems,
+}
+
+impl RevenueError {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            RevenueError::Malformed => "malformed",
+            RevenueError::InvalidAmount(_) => "invalid_amount",
+            RevenueError::InvalidCurrency(_) => "invalid_currency",
+            RevenueError::InvalidOrderId => "invalid_order_id",
+            RevenueError::InvalidItems => "invalid_items",
+        }
+    }
+}
+
+/// Validated revenue, with the amount converted into the site's reporting currency
+#[derive(Debug, Clone, Default)]
+pub struct Revenue {
+    pub amount: f64,
+    pub currency: String,
+    pub order_id: Option<String>,
+    pub items: Vec<RevenueItem>,
+    pub reporting_currency: String,
+    /// `None` when there is no exchange rate between the currencies
+    pub reporting_amount: Option<f64>,
+}
+
+impl Revenue {
+    /// Validate the revenue sent by the client and convert it into the reporting currency using the loaded exchange rates
+    pub fn from_raw(value: &serde_json::Value, reporting_currency: &str) -> Result<Self, RevenueError> {
+        let raw = RawRevenue::deserialize(value).map_err(|_| RevenueError::Malformed)?;
+
+        let amount = match &raw.amount {
+            RawAmount::Number(amount) => Some(*amount),
+            RawAmount::Text(amount) => amount.trim().parse().ok(),
+        }
+        .filter(|amount| is_valid_amount(*amount))
+        .ok_or_else(|| RevenueError::InvalidAmount(match &raw.amount {
+            RawAmount::Number(amount) => amount.to_string(),
+            RawAmount::Text(amount) => amount.clone(),
+        }))?;
+
+        let raw_currency = raw.currency.unwrap_or_default();
+        let currency = normalize_currency(&raw_currency)
+            .ok_or(RevenueError::InvalidCurrency(raw_currency))?;
+
+        let order_id = match raw.order_id.as_deref().map(str::trim) {
+            None => None,
+            Some(order_id) if order_id.is_empty() || order_id.chars().count() > MAX_ORDER_ID_LENGTH => {
+                return Err(RevenueError::InvalidOrderId);
+            }
+            Some(order_id) => Some(order_id.to_string()),
+        };
+
+        if raw.items.len() > MAX_ITEMS || !raw.items.iter().all(is_valid_item) {
+            return Err(RevenueError::InvalidItems);
+        }
+
+        // A low-value source currency can convert to more than the Decimal(18, 4) columns hold
+        let reporting_amount = get_exchange_rates()
+            .convert(amount, &currency, reporting_currency)
+            .filter(|reporting_amount| is_valid_amount(*reporting_amount));
+
+        Ok(Self {
+            amount,
+            currency,
+            order_id,
+            items: raw.items,
+            reporting_currency: reporting_currency.to_string(),
+            reporting_amount,
+        })
+    }
+}
+
+fn is_valid_amount(amount: f64) -> bool {
+    amount.is_finite() && (0.0..=MAX_AMOUNT).contains(&amount)
+}
+
+fn is_valid_item(item: &RevenueItem) -> bool {
+    let valid_text = |value: &Option<String>| value.as_ref().is_none_or(|value| value.chars().count() <= MAX_ITEM_FIELD_LENGTH);
+    let valid_quantity = item.quantity.is_none_or(|quantity| quantity >= 1.0 && quantity.fract() == 0.0 && quantity <= u32::MAX as f64);
+
+    valid_text(&item.id) && valid_text(&item.name) && valid_quantity && item.price.is_none_or(is_valid_amount)
+}
+
+/// Scale an amount for a Decimal(18, 4) column
+pub fn to_decimal(amount: f64) -> i64 {
+    (amount * DECIMAL_SCALE).round() as i64
+}
+
+pub fn initialize(exchange_rates_path: Option<&Path>) {
+    let Some(exchange_rates_path) = exchange_rates_path else {
+        info!("No EXCHANGE_RATES_PATH configured, revenue is only converted between identical currencies");
+        return;
+    };
+
+    info!("Initializing exchange rates from: {:?}", exchange_rates_path);
+    if let Err(e) = reload(exchange_rates_path) {
+        error!("Could not load exchange rates from {:?}: {:#}. Revenue will not be converted.", exchange_rates_path, e);
+    }
+}
+
+/// Load and swap in the exchange rates at the given path
+pub fn reload(exchange_rates_path: &Path) -> Result<()> {
+    let exchange_rates = load_exchange_rates(exchange_rates_path)?;
+    info!("Loaded {} exchange rates from {:?}", exchange_rates.len(), exchange_rates_path);
+    replace_exchange_rates(exchange_rates);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use currency::ExchangeRates;
+    use serde_json::json;
+
+    #[test]
+    fn accepts_numeric_string_amounts() {
+        let revenue = Revenue::from_raw(&json!({ "amount": "59.90", "currency": "eur" }), "EUR").unwrap();
+
+        assert_eq!(revenue.amount, 59.9);
+        assert_eq!(revenue.currency, "EUR");
+        assert_eq!(to_decimal(revenue.amount), 599_000);
+    }
+
+    #[test]
+    fn rejects_invalid_revenue_without_failing_deserialization() {
+        let reason = |value: serde_json::Value| Revenue::from_raw(&value, "EUR").unwrap_err().reason();
+
+        assert_eq!(reason(json!({ "amount": 10 })), "invalid_currency");
+        assert_eq!(reason(json!({ "amount": "ten", "currency": "EUR" })), "invalid_amount");
+        assert_eq!(reason(json!({ "amount": 1e12, "currency": "EUR" })), "invalid_amount");
+        assert_eq!(reason(json!({ "currency": "EUR" })), "malformed");
+        assert_eq!(reason(json!("59.90")), "malformed");
+    }
+
+    #[test]
+    fn drops_reporting_amounts_beyond_the_maximum() {
+        replace_exchange_rates(ExchangeRates::parse(r#"{"base": "USD", "rates": {"IRR": 42000, "EUR": 0.9}}"#).unwrap());
+
+        let revenue = Revenue::from_raw(&json!({ "amount": 50_000_000_000.0, "currency": "EUR" }), "IRR").unwrap();
+        assert_eq!(revenue.amount, 50_000_000_000.0);
+        assert_eq!(revenue.reporting_amount, None);
+
+        let revenue = Revenue::from_raw(&json!({ "amount": 90, "currency": "EUR" }), "IRR").unwrap();
+        assert_eq!(revenue.reporting_amount.map(f64::round), Some(4_200_000.0));
+        assert!(to_decimal(MAX_AMOUNT) < i64::MAX);
+    }
+}
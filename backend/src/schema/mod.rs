@@ -0,0 +1,241 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::properties::{CustomEventProperties, PropertyKind};
+
+/// What happens to custom events that do not conform to the site's event schema
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaMode {
+    /// Store the event, tagged with its violations
+    #[default]
+    Lenient,
+    /// Drop the event
+    Strict,
+}
+
+impl SchemaMode {
+    /// Action taken on non-conforming events, used as the metrics and violations table label
+    pub fn action(&self) -> &'static str {
+        match self {
+            SchemaMode::Lenient => "tagged",
+            SchemaMode::Strict => "rejected",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyType {
+    #[default]
+    Any,
+    String,
+    Number,
+    Boolean,
+    Array,
+}
+
+impl PropertyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropertyType::Any => "any",
+            PropertyType::String => "string",
+            PropertyType::Number => "number",
+            PropertyType::Boolean => "boolean",
+            PropertyType::Array => "array",
+        }
+    }
+
+    fn accepts(&self, kind: PropertyKind) -> bool {
+        match self {
+            PropertyType::Any => true,
+            PropertyType::String => kind == PropertyKind::String,
+            PropertyType::Number => kind == PropertyKind::Number,
+            PropertyType::Boolean => kind == PropertyKind::Boolean,
+            PropertyType::Array => kind == PropertyKind::Array,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PropertySchema {
+    #[serde(rename = "type", default)]
+    pub property_type: PropertyType,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Allowed properties of one custom event, keyed by flattened property name (e.g. "cart.items")
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventDefinition {
+    #[serde(default)]
+    pub properties: HashMap<String, PropertySchema>,
+    /// Accept properties that are not declared
+    #[serde(default)]
+    pub allow_additional_properties: bool,
+}
+
+/// Per-site registry of allowed custom events, as written in the site config file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventSchema {
+    #[serde(default)]
+    pub mode: SchemaMode,
+    /// Allowed event names, matched exactly, so "signup" and "Signup" are different events
+    #[serde(default)]
+    pub events: HashMap<String, EventDefinition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    UnknownEvent,
+    MissingProperty,
+    UnexpectedProperty,
+    InvalidType,
+}
+
+impl ViolationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ViolationKind::UnknownEvent => "unknown_event",
+            ViolationKind::MissingProperty => "missing_property",
+            ViolationKind::UnexpectedProperty => "unexpected_property",
+            ViolationKind::InvalidType => "invalid_type",
+        }
+    }
+}
+
+/// A way in which a custom event does not conform to the site's event schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub kind: ViolationKind,
+    /// Flattened property name, empty for unknown events
+    pub property: String,
+    pub detail: String,
+}
+
+impl SchemaViolation {
+    /// Compact form stored on tagged events, e.g. "missing_property:plan"
+    pub fn tag(&self) -> String {
+        if self.property.is_empty() {
+            self.kind.as_str().to_string()
+        } else {
+            format!("{}:{}", self.kind.as_str(), self.property)
+        }
+    }
+}
+
+impl EventSchema {
+    /// Check a custom event against the schema, returning all violations
+    pub fn validate(&self, event_name: &str, properties: &CustomEventProperties) -> Vec<SchemaViolation> {
+        let Some(definition) = self.events.get(event_name) else {
+            return vec![SchemaViolation {
+                kind: ViolationKind::UnknownEvent,
+                property: String::new(),
+                detail: format!("event '{}' is not declared", event_name),
+            }];
+        };
+
+        let mut violations = Vec::new();
+
+        for (name, schema) in &definition.properties {
+            match properties.kind_of(name) {
+                None if schema.required => violations.push(SchemaViolation {
+                    kind: ViolationKind::MissingProperty,
+                    property: name.clone(),
+                    detail: format!("required property '{}' is missing", name),
+                }),
+                Some(kind) if !schema.property_type.accepts(kind) => violations.push(SchemaViolation {
+                    kind: ViolationKind::InvalidType,
+                    property: name.clone(),
+                    detail: format!("expected {}, got {}", schema.property_type.as_str(), kind.as_str()),
+                }),
+                _ => {}
+            }
+        }
+
+        if !definition.allow_additional_properties {
+            for name in properties.keys().filter(|name| !definition.properties.contains_key(*name)) {
+                violations.push(SchemaViolation {
+                    kind: ViolationKind::UnexpectedProperty,
+                    property: name.to_string(),
+                    detail: format!("property '{}' is not declared", name),
+                });
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::properties::parse_properties;
+
+    fn schema(mode: &str) -> EventSchema {
+        serde_json::from_value(serde_json::json!({
+            "mode": mode,
+            "events": {
+                "signup": {
+                    "properties": {
+                        "plan": { "type": "string", "required": true },
+                        "cart.items": { "type": "number" },
+                        "tags": { "type": "array" }
+                    }
+                },
+                "download": { "allow_additional_properties": true }
+            }
+        }))
+        .unwrap()
+    }
+
+    fn validate(schema: &EventSchema, event_name: &str, json: &str) -> Vec<String> {
+        let (properties, rejections) = parse_properties(json);
+        assert!(rejections.is_empty(), "{:?}", rejections);
+        let mut tags: Vec<String> = schema.validate(event_name, &properties).iter().map(SchemaViolation::tag).collect();
+        tags.sort();
+        tags
+    }
+
+    #[test]
+    fn parses_mode() {
+        assert_eq!(schema("strict").mode, SchemaMode::Strict);
+        assert_eq!(schema("lenient").mode, SchemaMode::Lenient);
+        assert_eq!(EventSchema::default().mode, SchemaMode::Lenient);
+        assert_eq!(SchemaMode::Strict.action(), "rejected");
+        assert_eq!(SchemaMode::Lenient.action(), "tagged");
+    }
+
+    #[test]
+    fn accepts_conforming_events() {
+        let schema = schema("strict");
+
+        assert!(validate(&schema, "signup", r#"{"plan": "pro", "cart": {"items": 3}, "tags": ["a"]}"#).is_empty());
+        assert!(validate(&schema, "signup", r#"{"plan": "pro"}"#).is_empty());
+        assert!(validate(&schema, "download", r#"{"file": "report.pdf", "size": 1024}"#).is_empty());
+    }
+
+    #[test]
+    fn reports_unknown_events() {
+        let schema = schema("strict");
+
+        assert_eq!(validate(&schema, "purchase", r#"{"plan": "pro"}"#), ["unknown_event"]);
+        // Event names are matched exactly
+        assert_eq!(validate(&schema, "Signup", r#"{"plan": "pro"}"#), ["unknown_event"]);
+    }
+
+    #[test]
+    fn reports_missing_unexpected_and_mistyped_properties() {
+        let schema = schema("lenient");
+
+        assert_eq!(validate(&schema, "signup", "{}"), ["missing_property:plan"]);
+        assert_eq!(validate(&schema, "signup", r#"{"plan": "pro", "coupon": "X"}"#), ["unexpected_property:coupon"]);
+        assert_eq!(
+            validate(&schema, "signup", r#"{"plan": 3, "cart": {"items": "three"}, "tags": "a"}"#),
+            ["invalid_type:cart.items", "invalid_type:plan", "invalid_type:tags"]
+        );
+
+        let (properties, _) = parse_properties(r#"{"plan": true}"#);
+        let violations = schema.validate("signup", &properties);
+        assert_eq!(violations[0].detail, "expected string, got boolean");
+    }
+}
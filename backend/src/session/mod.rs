@@ -1,12 +1,96 @@
 use anyhow::Result;
+use moka::Expiry;
 use moka::sync::Cache;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use once_cell::sync::Lazy;
+use crate::campaign::CampaignInfo;
+use crate::referrer::{ReferrerInfo, ReferrerSource};
 
 const SESSION_EXPIRY: Duration = Duration::from_secs(30 * 60);
 
+/// First-touch referrer and campaign of a session
+#[derive(Debug, Clone, Default)]
+pub struct SessionAttribution {
+    pub referrer_source: Option<String>,
+    pub referrer_source_name: Option<String>,
+    pub referrer_url: Option<String>,
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+}
+
+impl SessionAttribution {
+    /// Attribution carried by a single event. Returns `None` for internal and direct
+    /// navigation without campaign parameters and for spam referrers, which must not replace the session's first touch.
+    pub fn from_event(referrer_info: &ReferrerInfo, campaign_info: &CampaignInfo) -> Option<Self> {
+        if referrer_info.source_type == ReferrerSource::Spam {
+            return None;
+        }
+
+        let has_campaign = campaign_info.utm_source.is_some()
+            || campaign_info.utm_medium.is_some()
+            || campaign_info.utm_campaign.is_some();
+        let has_external_referrer = !matches!(
+            referrer_info.source_type,
+            ReferrerSource::Direct | ReferrerSource::Internal
+        );
+
+        if !has_campaign && !has_external_referrer {
+            return None;
+        }
+
+        Some(Self {
+            referrer_source: Some(referrer_info.source_type.as_str().to_string()),
+            referrer_source_name: referrer_info.source_name.clone(),
+            referrer_url: referrer_info.url.clone(),
+            utm_source: campaign_info.utm_source.clone(),
+            utm_medium: campaign_info.utm_medium.clone(),
+            utm_campaign: campaign_info.utm_campaign.clone(),
+            utm_term: campaign_info.utm_term.clone(),
+            utm_content: campaign_info.utm_content.clone(),
+        })
+    }
+}
+
+/// State kept for each active session
+#[derive(Debug, Clone)]
+pub struct SessionState {
+    pub session_id: String,
+    /// First non-internal referrer or campaign seen in the session, if any
+    pub attribution: Option<SessionAttribution>,
+}
+
+/// Sessions expire 30 minutes after their last event. Unlike a time-to-idle policy, reads don't extend
+/// a session, so looking one up (e.g. to issue a cross-domain token) is not counted as activity.
+struct SessionExpiry;
+
+impl Expiry<String, SessionState> for SessionExpiry {
+    fn expire_after_create(&self, _key: &String, _value: &SessionState, _created_at: Instant) -> Option<Duration> {
+        Some(SESSION_EXPIRY)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        _value: &SessionState,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(SESSION_EXPIRY)
+    }
+}
+
 // Moka cache with time-based eviction (sessions expire after 30 minutes of inactivity)
-static SESSION_CACHE: Lazy<Cache<String, String>> = Lazy::new(|| {
+static SESSION_CACHE: Lazy<Cache<String, SessionState>> = Lazy::new(|| {
+    Cache::builder()
+        .expire_after(SessionExpiry)
+        .build()
+});
+
+// Fingerprints seen on another domain of a cross-domain site, mapped to the visitor they were linked to
+static VISITOR_ALIASES: Lazy<Cache<String, String>> = Lazy::new(|| {
     Cache::builder()
         .time_to_idle(SESSION_EXPIRY)
         .build()
@@ -17,22 +101,150 @@ fn generate_session_id() -> String {
     nanoid::nanoid!(16)
 }
 
-/// Get or create a session ID for a visitor
-pub fn get_or_create_session_id(
+/// Get or create the session for a visitor, recording the event's attribution
+/// if the session does not have a first touch yet
+pub fn get_or_create_session(
     site_id: &str,
     visitor_fingerprint: &str,
-) -> Result<String> {
+    attribution: Option<SessionAttribution>,
+) -> Result<SessionState> {
     let cache_key = format!("{}-{}", site_id, visitor_fingerprint);
-    
-    // Check if the session ID exists in the cache - this will refresh the idle timer
-    if let Some(session_id) = SESSION_CACHE.get(&cache_key) {
-        return Ok(session_id);
-    }
-    
-    // Create a new session if one doesn't exist or was evicted due to inactivity
-    let new_session_id = generate_session_id();
-    
-    SESSION_CACHE.insert(cache_key, new_session_id.clone());
-    
-    Ok(new_session_id)
-}
\ No newline at end of file
+
+    // Upserting refreshes the idle timer, and is atomic so concurrent events share one session
+    let entry = SESSION_CACHE.entry(cache_key).and_upsert_with(|existing| match existing {
+        Some(entry) => {
+            let mut session = entry.into_value();
+            if session.attribution.is_none() {
+                session.attribution = attribution;
+            }
+            session
+        }
+        // Create a new session if one doesn't exist or was evicted due to inactivity
+        None => SessionState {
+            session_id: generate_session_id(),
+            attribution,
+        },
+    });
+
+    Ok(entry.into_value())
+}
+
+/// The active session of a visitor, without creating or extending it
+pub fn find_session(site_id: &str, visitor_fingerprint: &str) -> Option<SessionState> {
+    SESSION_CACHE.get(&format!("{}-{}", site_id, visitor_fingerprint))
+}
+
+/// Link a fingerprint to a visitor and session carried over from another domain of the same site.
+/// The session is adopted if this server no longer has it.
+pub fn link_visitor(site_id: &str, visitor_fingerprint: &str, visitor_id: &str, session_id: &str) {
+    if visitor_fingerprint != visitor_id {
+        VISITOR_ALIASES.insert(format!("{}-{}", site_id, visitor_fingerprint), visitor_id.to_string());
+    }
+
+    SESSION_CACHE
+        .entry(format!("{}-{}", site_id, visitor_id))
+        .or_insert_with(|| SessionState {
+            session_id: session_id.to_string(),
+            attribution: None,
+        });
+}
+
+/// The visitor a fingerprint belongs to, following cross-domain links
+pub fn resolve_visitor(site_id: &str, visitor_fingerprint: &str) -> String {
+    VISITOR_ALIASES
+        .get(&format!("{}-{}", site_id, visitor_fingerprint))
+        .unwrap_or_else(|| visitor_fingerprint.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn referrer(source_type: ReferrerSource, source_name: Option<&str>) -> ReferrerInfo {
+        ReferrerInfo {
+            url: source_name.map(|name| format!("{}.example/", name.to_lowercase())),
+            source_type,
+            source_name: source_name.map(str::to_string),
+            search_term: None,
+        }
+    }
+
+    fn campaign(source: &str) -> CampaignInfo {
+        CampaignInfo {
+            utm_source: Some(source.to_string()),
+            utm_medium: Some("email".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ignores_events_without_external_touch() {
+        let no_campaign = CampaignInfo::default();
+
+        assert!(SessionAttribution::from_event(&referrer(ReferrerSource::Direct, None), &no_campaign).is_none());
+        assert!(SessionAttribution::from_event(&referrer(ReferrerSource::Internal, Some("Shop")), &no_campaign).is_none());
+        assert!(SessionAttribution::from_event(&referrer(ReferrerSource::Spam, Some("Spammer")), &no_campaign).is_none());
+        assert!(SessionAttribution::from_event(&referrer(ReferrerSource::Spam, Some("Spammer")), &campaign("spam")).is_none());
+
+        let attribution = SessionAttribution::from_event(&referrer(ReferrerSource::Internal, Some("Shop")), &campaign("newsletter")).unwrap();
+        assert_eq!(attribution.referrer_source.as_deref(), Some("internal"));
+        assert_eq!(attribution.utm_source.as_deref(), Some("newsletter"));
+
+        let attribution = SessionAttribution::from_event(&referrer(ReferrerSource::Search, Some("Google")), &no_campaign).unwrap();
+        assert_eq!(attribution.referrer_source_name.as_deref(), Some("Google"));
+    }
+
+    #[test]
+    fn keeps_first_touch_for_the_session() {
+        let site_id = "first-touch-site";
+        let first = get_or_create_session(site_id, "visitor", None).unwrap();
+        assert!(first.attribution.is_none());
+
+        // Direct and spam hits carry no attribution and leave the session unattributed
+        let spam = SessionAttribution::from_event(&referrer(ReferrerSource::Spam, Some("Spammer")), &CampaignInfo::default());
+        assert!(get_or_create_session(site_id, "visitor", spam).unwrap().attribution.is_none());
+
+        let search = SessionAttribution::from_event(&referrer(ReferrerSource::Search, Some("Google")), &CampaignInfo::default());
+        let session = get_or_create_session(site_id, "visitor", search).unwrap();
+        assert_eq!(session.session_id, first.session_id);
+        assert_eq!(session.attribution.as_ref().and_then(|a| a.referrer_source_name.as_deref()), Some("Google"));
+
+        // A later campaign hit in the same session does not replace the first touch
+        let email = SessionAttribution::from_event(&referrer(ReferrerSource::Direct, None), &campaign("newsletter"));
+        let session = get_or_create_session(site_id, "visitor", email).unwrap();
+        assert_eq!(session.session_id, first.session_id);
+        let attribution = session.attribution.unwrap();
+        assert_eq!(attribution.referrer_source.as_deref(), Some("search"));
+        assert_eq!(attribution.utm_source, None);
+    }
+
+    #[test]
+    fn resolves_cross_domain_visitor_aliases() {
+        let site_id = "cross-domain-site";
+        let original = get_or_create_session(site_id, "visitor-a", None).unwrap();
+
+        link_visitor(site_id, "fingerprint-b", "visitor-a", &original.session_id);
+        assert_eq!(resolve_visitor(site_id, "fingerprint-b"), "visitor-a");
+        assert_eq!(resolve_visitor(site_id, "fingerprint-c"), "fingerprint-c");
+        assert_eq!(resolve_visitor("other-site", "fingerprint-b"), "fingerprint-b");
+
+        // A session this server no longer has is adopted from the token
+        link_visitor(site_id, "fingerprint-d", "visitor-e", "carried-session");
+        assert_eq!(resolve_visitor(site_id, "fingerprint-d"), "visitor-e");
+        assert_eq!(get_or_create_session(site_id, "visitor-e", None).unwrap().session_id, "carried-session");
+
+        // Linking never replaces a session the visitor already has
+        link_visitor(site_id, "fingerprint-f", "visitor-a", "other-session");
+        assert_eq!(get_or_create_session(site_id, "visitor-a", None).unwrap().session_id, original.session_id);
+    }
+
+    #[test]
+    fn finding_a_session_does_not_create_one() {
+        let site_id = "find-session-site";
+        assert!(find_session(site_id, "visitor").is_none());
+        assert!(find_session(site_id, "visitor").is_none());
+
+        let session = get_or_create_session(site_id, "visitor", None).unwrap();
+        assert_eq!(find_session(site_id, "visitor").map(|found| found.session_id), Some(session.session_id));
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Result, Context};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use tracing::{info, error};
+use crate::campaign::CampaignSettings;
+use crate::channel::{ChannelRuleDefinition, ChannelRuleSet};
+use crate::normalization::{UrlNormalizationSettings, UrlNormalizer};
+use crate::schema::EventSchema;
+use crate::revenue;
+
+/// Per-site settings as written in the site config file
+#[derive(Debug, Default, Deserialize)]
+struct SiteSettings {
+    /// Channel rules evaluated before the default channel rules
+    #[serde(default)]
+    channel_rules: Vec<ChannelRuleDefinition>,
+    /// Campaign parameter aliases and normalization
+    #[serde(default)]
+    campaign: CampaignSettings,
+    /// Additional domains (and their subdomains) that count as the same property for internal referrers
+    #[serde(default)]
+    internal_domains: Vec<String>,
+    /// Rules that map URL variants of the same page onto one path
+    #[serde(default)]
+    url_normalization: UrlNormalizationSettings,
+    /// Allowed custom events and their properties, not enforced when absent
+    #[serde(default)]
+    event_schema: Option<EventSchema>,
+    /// ISO 4217 currency revenue is converted into, DEFAULT_REPORTING_CURRENCY when absent
+    #[serde(default)]
+    reporting_currency: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SiteConfigFile {
+    #[serde(default)]
+    sites: HashMap<String, SiteSettings>,
+}
+
+/// Per-site processing settings, compiled from the site config file
+#[derive(Debug, Default)]
+pub struct SiteConfig {
+    pub channel_rules: Option<ChannelRuleSet>,
+    pub campaign: CampaignSettings,
+    pub internal_domains: Vec<String>,
+    pub url_normalizer: UrlNormalizer,
+    pub event_schema: Option<EventSchema>,
+    pub reporting_currency: Option<String>,
+}
+
+impl SiteConfig {
+    fn compile(site_id: &str, settings: SiteSettings) -> Result<Self> {
+        let channel_rules = if settings.channel_rules.is_empty() {
+            None
+        } else {
+            Some(ChannelRuleSet::compile(settings.channel_rules)
+                .with_context(|| format!("Invalid channel rules for site {}", site_id))?)
+        };
+
+        let url_normalizer = UrlNormalizer::compile(settings.url_normalization)
+            .with_context(|| format!("Invalid URL normalization rules for site {}", site_id))?;
+
+        let reporting_currency = settings.reporting_currency
+            .map(|currency| revenue::normalize_currency(&currency)
+                .with_context(|| format!("Invalid reporting currency '{}' for site {}", currency, site_id)))
+            .transpose()?;
+
+        Ok(Self {
+            channel_rules,
+            campaign: settings.campaign,
+            internal_domains: settings.internal_domains
+                .into_iter()
+                .map(|domain| domain.trim().trim_start_matches("*.").to_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect(),
+            url_normalizer,
+            event_schema: settings.event_schema,
+            reporting_currency,
+        })
+    }
+}
+
+type SiteConfigMap = HashMap<String, Arc<SiteConfig>>;
+
+static SITE_CONFIGS: Lazy<RwLock<Arc<SiteConfigMap>>> = Lazy::new(|| RwLock::new(Arc::new(HashMap::new())));
+
+static DEFAULT_SITE_CONFIG: Lazy<Arc<SiteConfig>> = Lazy::new(|| Arc::new(SiteConfig::default()));
+
+pub fn initialize(site_config_path: Option<&Path>) {
+    let Some(site_config_path) = site_config_path else {
+        info!("No SITE_CONFIG_PATH configured, using default settings for all sites");
+        return;
+    };
+
+    info!("Initializing site config from: {:?}", site_config_path);
+    if let Err(e) = reload(site_config_path) {
+        error!("Could not load site config from {:?}: {:#}. Using default settings for all sites.", site_config_path, e);
+    }
+}
+
+/// Load and validate the site config file, then swap it in
+pub fn reload(site_config_path: &Path) -> Result<()> {
+    let contents = fs::read_to_string(site_config_path)
+        .with_context(|| format!("Failed to read site config {:?}", site_config_path))?;
+    let file: SiteConfigFile = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse site config {:?}", site_config_path))?;
+
+    let configs = file.sites
+        .into_iter()
+        .map(|(site_id, settings)| {
+            let config = SiteConfig::compile(&site_id, settings)?;
+            Ok((site_id, Arc::new(config)))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    info!("Loaded site config for {} sites", configs.len());
+    *SITE_CONFIGS.write().unwrap() = Arc::new(configs);
+    Ok(())
+}
+
+/// Settings for a site, falling back to the defaults for sites without an entry
+pub fn get_site_config(site_id: &str) -> Arc<SiteConfig> {
+    SITE_CONFIGS.read().unwrap()
+        .get(site_id)
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_SITE_CONFIG.clone())
+}
@@ -0,0 +1,238 @@
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use super::{ParsedUserAgent, UaDeviceClass};
+
+/// Client hint headers requested from browsers through `Accept-CH`
+pub const ACCEPT_CH: &str = "Sec-CH-UA, Sec-CH-UA-Platform, Sec-CH-UA-Platform-Version, Sec-CH-UA-Mobile, Sec-CH-UA-Model";
+
+/// Hint values longer than this are ignored. Hints sent by the tracker are not limited by the browser.
+const MAX_HINT_LENGTH: usize = 64;
+
+/// A single entry of the `Sec-CH-UA` brand list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrandVersion {
+    pub brand: String,
+    pub version: String,
+}
+
+/// User-Agent Client Hints, either collected by the tracker through `navigator.userAgentData`
+/// or read from `Sec-CH-UA-*` request headers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientHints {
+    #[serde(default)]
+    pub brands: Vec<BrandVersion>,
+    pub platform: Option<String>,
+    pub platform_version: Option<String>,
+    pub mobile: Option<bool>,
+    pub model: Option<String>,
+}
+
+impl ClientHints {
+    /// Read client hints from `Sec-CH-UA-*` request headers. Returns `None` if none are present.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        let hints = ClientHints {
+            brands: header("sec-ch-ua").map(parse_brand_list).unwrap_or_default(),
+            platform: header("sec-ch-ua-platform").and_then(unquote),
+            platform_version: header("sec-ch-ua-platform-version").and_then(unquote),
+            mobile: header("sec-ch-ua-mobile").and_then(|value| match value.trim() {
+                "?1" => Some(true),
+                "?0" => Some(false),
+                _ => None,
+            }),
+            model: header("sec-ch-ua-model").and_then(unquote),
+        };
+
+        if hints.is_empty() { None } else { Some(hints) }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.brands.is_empty()
+            && self.platform.is_none()
+            && self.platform_version.is_none()
+            && self.mobile.is_none()
+            && self.model.is_none()
+    }
+
+    /// Fill fields missing from these hints with values from another source
+    pub fn merge(self, other: ClientHints) -> Self {
+        ClientHints {
+            brands: if self.brands.is_empty() { other.brands } else { self.brands },
+            platform: self.platform.or(other.platform),
+            platform_version: self.platform_version.or(other.platform_version),
+            mobile: self.mobile.or(other.mobile),
+            model: self.model.or(other.model),
+        }
+    }
+
+    /// The most specific known browser brand and its family, ignoring GREASE entries and unknown brands.
+    /// The generic Chromium brand is only used when no other brand is known.
+    fn primary_brand(&self) -> Option<(&BrandVersion, &'static str)> {
+        let mobile = self.mobile.unwrap_or(false);
+        let known_brands: Vec<(&BrandVersion, &'static str)> = self.brands.iter()
+            .filter(|b| b.version.len() <= MAX_HINT_LENGTH)
+            .filter_map(|b| browser_family(&b.brand, mobile).map(|family| (b, family)))
+            .collect();
+
+        known_brands.iter()
+            .find(|(b, _)| b.brand != "Chromium")
+            .or_else(|| known_brands.first())
+            .copied()
+    }
+
+    /// OS version from the platform version hint. Windows 11 reports platform versions 13 and above.
+    pub fn os_version(&self) -> Option<String> {
+        let platform_version = bounded(self.platform_version.as_deref())?;
+        let mut parts = platform_version.split('.');
+        let major: u32 = parts.next()?.parse().ok()?;
+        let minor = parts.next().unwrap_or("0");
+
+        if self.platform.as_deref() == Some("Windows") {
+            return Some(match major {
+                13.. => "11".to_string(),
+                1.. => "10".to_string(),
+                // Platform version 0 covers Windows 7, 8 and 8.1
+                _ => "8.1".to_string(),
+            });
+        }
+
+        Some(format!("{}.{}", major, minor))
+    }
+
+    /// Override values parsed from the user agent string with the values from the hints
+    pub fn apply(&self, parsed: &mut ParsedUserAgent) {
+        if let Some((brand, family)) = self.primary_brand() {
+            parsed.browser = family.to_string();
+            parsed.browser_version = brand.version.split('.').next()
+                .filter(|major| !major.is_empty())
+                .map(|major| major.to_string());
+            // The brand list usually only carries the major version; keep the UA string's full version when it agrees
+            let full_version_matches = parsed.browser_version_full.as_deref()
+                .and_then(|full| full.split('.').next())
+                == parsed.browser_version.as_deref();
+            if !full_version_matches {
+                parsed.browser_version_full = Some(brand.version.clone()).filter(|v| !v.is_empty());
+            }
+            // Only Chromium based browsers implement client hints
+            parsed.browser_engine = Some("Blink".to_string());
+        }
+
+        if let Some(platform) = bounded(self.platform.as_deref()) {
+            parsed.os = os_family(platform);
+        }
+
+        if let Some(os_version) = self.os_version() {
+            let (major, minor) = match os_version.split_once('.') {
+                Some((major, minor)) => (major.to_string(), Some(minor.to_string())),
+                None => (os_version, None),
+            };
+            parsed.os_version_major = Some(major);
+            parsed.os_version_minor = minor;
+        }
+
+        if let Some(model) = bounded(self.model.as_deref()) {
+            parsed.device_model = Some(model.to_string());
+        }
+
+        match self.mobile {
+            Some(true) => parsed.device_class = Some(UaDeviceClass::Mobile),
+            // Android tablets report `?0` while sending a phone-like user agent
+            Some(false) if parsed.device_class == Some(UaDeviceClass::Mobile) => {
+                parsed.device_class = Some(if parsed.os == "Android" {
+                    UaDeviceClass::Tablet
+                } else {
+                    UaDeviceClass::Desktop
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Map client hint brands to the browser families emitted by regexes.yaml. Returns `None` for other brands,
+/// so the family parsed from the user agent string is kept.
+fn browser_family(brand: &str, mobile: bool) -> Option<&'static str> {
+    let family = match (brand, mobile) {
+        ("Google Chrome", false) => "Chrome",
+        ("Google Chrome", true) => "Chrome Mobile",
+        ("Chromium", false) => "Chromium",
+        ("Chromium", true) => "Chrome Mobile",
+        ("Microsoft Edge", false) => "Edge",
+        ("Microsoft Edge", true) => "Edge Mobile",
+        ("Opera" | "Opera GX", false) => "Opera",
+        ("Opera" | "Opera GX", true) => "Opera Mobile",
+        ("YaBrowser" | "Yandex", _) => "Yandex Browser",
+        ("Brave", _) => "Brave",
+        ("Samsung Internet", _) => "Samsung Internet",
+        ("Vivaldi", _) => "Vivaldi",
+        ("Whale" | "NAVER Whale", _) => "Whale",
+        _ => return None,
+    };
+    Some(family)
+}
+
+/// A non-empty hint value within the length limit
+fn bounded(value: Option<&str>) -> Option<&str> {
+    value.filter(|value| !value.is_empty() && value.len() <= MAX_HINT_LENGTH)
+}
+
+/// Map client hint platforms to the OS families used by regexes.yaml
+fn os_family(platform: &str) -> String {
+    match platform {
+        "macOS" => "Mac OS X",
+        "Chrome OS" | "ChromeOS" => "Chrome OS",
+        other => other,
+    }.to_string()
+}
+
+/// Strip the quotes from a structured header string value (e.g. `"Windows"`)
+fn unquote(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches('"');
+    if value.is_empty() { None } else { Some(value.to_string()) }
+}
+
+/// Parse a `Sec-CH-UA` brand list (e.g. `"Chromium";v="124", "Google Chrome";v="124"`)
+fn parse_brand_list(value: &str) -> Vec<BrandVersion> {
+    value.split(',')
+        .filter_map(|entry| {
+            let (brand, params) = entry.split_once(';')?;
+            let version = params.trim().strip_prefix("v=")?;
+            Some(BrandVersion {
+                brand: unquote(brand)?,
+                version: unquote(version).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hints(brands: &[(&str, &str)], mobile: bool) -> ClientHints {
+        ClientHints {
+            brands: brands.iter()
+                .map(|(brand, version)| BrandVersion { brand: brand.to_string(), version: version.to_string() })
+                .collect(),
+            mobile: Some(mobile),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn maps_brands_onto_regexes_families() {
+        let edge = hints(&[("Not.A/Brand", "99"), ("Chromium", "124"), ("Microsoft Edge", "124")], true);
+        assert_eq!(edge.primary_brand().map(|(_, family)| family), Some("Edge Mobile"));
+
+        let chromium = hints(&[("Chromium", "124"), ("Some New Browser", "1")], false);
+        assert_eq!(chromium.primary_brand().map(|(_, family)| family), Some("Chromium"));
+    }
+
+    #[test]
+    fn ignores_unknown_and_oversized_hints() {
+        assert!(hints(&[("Made Up Browser", "1")], false).primary_brand().is_none());
+        assert!(hints(&[("Google Chrome", &"1".repeat(MAX_HINT_LENGTH + 1))], false).primary_brand().is_none());
+        assert_eq!(bounded(Some(&"x".repeat(MAX_HINT_LENGTH + 1))), None);
+    }
+}
@@ -0,0 +1,58 @@
+/// User agent tokens added by in-app browsers and webviews, mapped to the app's source name.
+/// Checked in order, so more specific tokens come first (e.g. Messenger before Facebook).
+const IN_APP_SIGNATURES: &[(&str, &str)] = &[
+    ("Instagram", "Instagram"),
+    ("FBAN/Messenger", "Messenger"),
+    ("FB_IAB/MESSENGER", "Messenger"),
+    ("FBAN/", "Facebook"),
+    ("FBAV/", "Facebook"),
+    ("FB_IAB/", "Facebook"),
+    ("FBIOS", "Facebook"),
+    ("musical_ly", "TikTok"),
+    ("BytedanceWebview", "TikTok"),
+    ("TikTok", "TikTok"),
+    ("LinkedInApp", "LinkedIn"),
+    ("Snapchat", "Snapchat"),
+    ("Pinterest/", "Pinterest"),
+    ("Twitter", "Twitter"),
+    ("Line/", "LINE"),
+    ("WhatsApp", "WhatsApp"),
+    ("Telegram", "Telegram"),
+    ("Reddit/", "Reddit"),
+];
+
+/// Detect a known in-app browser from its user agent signature
+pub fn detect_in_app_source(user_agent: &str) -> Option<&'static str> {
+    IN_APP_SIGNATURES
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, source)| *source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_in_app_browsers() {
+        let cases = [
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 Instagram 325.0.3.32.95", "Instagram"),
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 [FBAN/FBIOS;FBAV/456.0.0.38.109]", "Facebook"),
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 [FBAN/MessengerForiOS;FBAV/448.0.0.36.109]", "Messenger"),
+            ("Mozilla/5.0 (Linux; Android 14; Pixel 8; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/124.0.0.0 Mobile Safari/537.36 [FB_IAB/MESSENGER;FBAV/450.0.0.37.109;]", "Messenger"),
+            ("Mozilla/5.0 (Linux; Android 14; SM-S918B; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/124.0.0.0 Mobile Safari/537.36 musical_ly_2023405020 BytedanceWebview/d8a21c6", "TikTok"),
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 [LinkedInApp]/9.29.6660", "LinkedIn"),
+            ("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148 Line/14.5.0", "LINE"),
+        ];
+
+        for (user_agent, source) in cases {
+            assert_eq!(detect_in_app_source(user_agent), Some(source), "{}", user_agent);
+        }
+    }
+
+    #[test]
+    fn ignores_regular_browsers() {
+        let safari = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1";
+        assert_eq!(detect_in_app_source(safari), None);
+    }
+}
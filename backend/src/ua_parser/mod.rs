@@ -1,74 +1,269 @@
 use std::path::Path;
 
+use anyhow::{Result, Context};
 use uaparser::{UserAgentParser, Parser};
 use once_cell::sync::Lazy;
 use moka::sync::Cache;
-use tracing::{info, debug};
-use std::sync::OnceLock;
+use tracing::{info, debug, error};
+use std::sync::{Arc, RwLock};
 
-static USER_AGENT_PARSER: OnceLock<UserAgentParser> = OnceLock::new();
+mod client_hints;
+mod in_app;
+pub use client_hints::{ACCEPT_CH, ClientHints};
 
-static UA_CACHE: Lazy<Cache<String, (String, Option<String>, String)>> = Lazy::new(|| {
+/// Active parser, swapped atomically on reload. `None` until a regexes file has loaded successfully.
+static USER_AGENT_PARSER: Lazy<RwLock<Option<Arc<UserAgentParser>>>> = Lazy::new(|| RwLock::new(None));
+
+/// A common desktop user agent that any usable regexes file must recognize
+const VALIDATION_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+
+static UA_CACHE: Lazy<Cache<String, ParsedUserAgent>> = Lazy::new(|| {
     Cache::builder()
         .max_capacity(5_000)
         .time_to_live(std::time::Duration::from_secs(3600))
         .build()
 });
 
+/// Device form factor implied by the user agent alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UaDeviceClass {
+    Mobile,
+    Tablet,
+    Desktop,
+}
+
 #[derive(Debug, Clone)]
 pub struct ParsedUserAgent {
     pub browser: String,
+    /// Browser major version, used for fingerprinting
     pub browser_version: Option<String>,
+    /// Browser version including minor and patch (e.g. "17.4" or "124.0.6367")
+    pub browser_version_full: Option<String>,
+    /// Rendering engine (e.g. "Blink", "WebKit", "Gecko")
+    pub browser_engine: Option<String>,
     pub os: String,
+    pub os_version_major: Option<String>,
+    pub os_version_minor: Option<String>,
+    /// Device family from regexes.yaml (e.g. "iPad", "Samsung SM-G991B")
+    pub device_family: Option<String>,
+    pub device_brand: Option<String>,
+    pub device_model: Option<String>,
+    pub device_class: Option<UaDeviceClass>,
+    /// App whose in-app browser or webview sent the request (e.g. "Instagram", "TikTok")
+    pub in_app_source: Option<String>,
 }
 
 pub fn initialize(ua_regexes_path: &Path) {
     info!("Initializing user agent parser from: {:?}", ua_regexes_path);
-    
-    USER_AGENT_PARSER.get_or_init(|| {
-        UserAgentParser::builder()
-            .with_unicode_support(false)  // Disable unicode since we don't expect any unicode in the user agent
-            .with_device(false)          // Disable device parsing since we detect device from screen resolution
-            .build_from_yaml(ua_regexes_path.to_str().unwrap_or(""))
-            .unwrap_or_else(|e| {
-                panic!("Failed to initialize user agent parser from {:?}: {}. Please ensure regexes.yaml is available.", ua_regexes_path, e);
-            })
-    });
-    
+
     Lazy::force(&UA_CACHE);
-    info!("User agent parser initialization complete");
+    match load_parser(ua_regexes_path) {
+        Ok(parser) => {
+            replace_parser(parser);
+            info!("User agent parser initialization complete");
+        }
+        Err(e) => {
+            error!("Failed to initialize user agent parser from {:?}: {:#}. User agents will be reported as \"Other\" until a valid regexes.yaml is loaded.", ua_regexes_path, e);
+        }
+    }
+}
+
+/// Build a parser from a regexes file and check that it recognizes a well-known user agent
+pub fn load_parser(ua_regexes_path: &Path) -> Result<UserAgentParser> {
+    let parser = UserAgentParser::builder()
+        .with_unicode_support(false)  // Disable unicode since we don't expect any unicode in the user agent
+        .build_from_yaml(ua_regexes_path.to_str().unwrap_or(""))
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .with_context(|| format!("Failed to parse user agent regexes {:?}", ua_regexes_path))?;
+
+    let client = parser.parse(VALIDATION_USER_AGENT);
+    if client.user_agent.family != "Chrome" || client.os.family != "Windows" {
+        anyhow::bail!(
+            "User agent regexes {:?} failed validation: parsed {} on {}",
+            ua_regexes_path, client.user_agent.family, client.os.family
+        );
+    }
+
+    Ok(parser)
 }
 
-/// Parse a user agent string and return browser, browser_version, and OS information
+/// Swap in a new parser and drop cached results produced by the previous one
+pub fn replace_parser(parser: UserAgentParser) {
+    *USER_AGENT_PARSER.write().unwrap() = Some(Arc::new(parser));
+    UA_CACHE.invalidate_all();
+}
+
+/// Load, validate and swap in the regexes file at the given path
+pub fn reload(ua_regexes_path: &Path) -> Result<()> {
+    let parser = load_parser(ua_regexes_path)?;
+    replace_parser(parser);
+    info!("User agent parser reloaded from {:?}", ua_regexes_path);
+    Ok(())
+}
+
+fn get_parser() -> Option<Arc<UserAgentParser>> {
+    USER_AGENT_PARSER.read().unwrap().clone()
+}
+
+/// Parse a user agent string and return browser, OS and device information
 pub fn parse_user_agent(user_agent: &str) -> ParsedUserAgent {
     debug!("Parsing user agent: {:?}", user_agent);
-    
-    if let Some((browser, browser_version, os)) = UA_CACHE.get(user_agent) {
-        debug!("User agent cache hit: browser={:?}, version={:?}, os={:?}", browser, browser_version, os);
-        return ParsedUserAgent {
-            browser,
-            browser_version,
-            os,
-        };
+
+    if let Some(parsed) = UA_CACHE.get(user_agent) {
+        debug!("User agent cache hit: {:?}", parsed);
+        return parsed;
     }
-    
-    let parser = USER_AGENT_PARSER.get().expect("User agent parser not initialized. Call initialize() first.");
+
+    let Some(parser) = get_parser() else {
+        return ParsedUserAgent::unknown();
+    };
     let client = parser.parse(user_agent);
-    
+
     let browser = client.user_agent.family.to_string();
-    let browser_version = client.user_agent.major.map(|v| v.to_string());
+    let browser_version = client.user_agent.major.as_ref().map(|v| v.to_string());
+    let browser_version_full = join_version(&[
+        client.user_agent.major.as_deref(),
+        client.user_agent.minor.as_deref(),
+        client.user_agent.patch.as_deref(),
+    ]);
     let os = client.os.family.to_string();
-    
-    UA_CACHE.insert(
-        user_agent.to_string(),
-        (browser.clone(), browser_version.clone(), os.clone())
-    );
-    
-    debug!("User agent parsed: browser={:?}, version={:?}, os={:?}", browser, browser_version, os);
-    
-    ParsedUserAgent {
+    let os_version_major = client.os.major.map(|v| v.to_string());
+    let os_version_minor = client.os.minor.map(|v| v.to_string());
+    let browser_engine = detect_engine(user_agent, &browser, &os).map(|engine| engine.to_string());
+
+    // regexes.yaml reports "Other" when no device rule matched
+    let device_family = Some(client.device.family.to_string()).filter(|family| family != "Other");
+    let device_brand = client.device.brand.map(|brand| brand.to_string());
+    let device_model = client.device.model.map(|model| model.to_string());
+    let device_class = classify_device(user_agent, &os, device_family.as_deref(), device_model.as_deref());
+    let in_app_source = in_app::detect_in_app_source(user_agent).map(|source| source.to_string());
+
+    let parsed = ParsedUserAgent {
         browser,
         browser_version,
+        browser_version_full,
+        browser_engine,
         os,
+        os_version_major,
+        os_version_minor,
+        device_family,
+        device_brand,
+        device_model,
+        device_class,
+        in_app_source,
+    };
+
+    UA_CACHE.insert(user_agent.to_string(), parsed.clone());
+
+    debug!("User agent parsed: {:?}", parsed);
+
+    parsed
+}
+
+/// Derive the device form factor from UA tokens and parsed device/OS data.
+/// Returns `None` when the user agent gives no reliable signal.
+fn classify_device(user_agent: &str, os: &str, device_family: Option<&str>, device_model: Option<&str>) -> Option<UaDeviceClass> {
+    let is_ipad = [device_family, device_model].iter().flatten().any(|value| value.starts_with("iPad"))
+        || user_agent.contains("iPad");
+
+    if is_ipad
+        // Firefox for Android tablets sends "Tablet;", whereas Windows may send "Tablet PC" on desktops
+        || user_agent.contains("Tablet;")
+        || user_agent.contains("Kindle")
+        || user_agent.contains("Silk/")
+        || user_agent.contains("PlayBook")
+    {
+        return Some(UaDeviceClass::Tablet);
+    }
+
+    if user_agent.contains("Mobi") || user_agent.contains("iPhone") || user_agent.contains("iPod") {
+        return Some(UaDeviceClass::Mobile);
+    }
+
+    // Android tablets omit the "Mobile" token that Android phones send
+    if os == "Android" {
+        return Some(UaDeviceClass::Tablet);
+    }
+
+    match os {
+        "Windows" | "Mac OS X" | "Linux" | "Ubuntu" | "Fedora" | "Chrome OS" => Some(UaDeviceClass::Desktop),
+        _ => None,
+    }
+}
+
+impl ParsedUserAgent {
+    /// Result used while no regexes file is loaded
+    fn unknown() -> Self {
+        ParsedUserAgent {
+            browser: "Other".to_string(),
+            browser_version: None,
+            browser_version_full: None,
+            browser_engine: None,
+            os: "Other".to_string(),
+            os_version_major: None,
+            os_version_minor: None,
+            device_family: None,
+            device_brand: None,
+            device_model: None,
+            device_class: None,
+            in_app_source: None,
+        }
+    }
+
+    /// OS version as "major.minor", or just "major" when no minor version is known
+    pub fn os_version(&self) -> Option<String> {
+        let major = self.os_version_major.as_deref()?;
+        Some(match self.os_version_minor.as_deref() {
+            Some(minor) => format!("{}.{}", major, minor),
+            None => major.to_string(),
+        })
+    }
+}
+
+/// Join the leading known version components (e.g. ["17", "4", None] -> "17.4")
+fn join_version(parts: &[Option<&str>]) -> Option<String> {
+    let known: Vec<&str> = parts.iter()
+        .map_while(|part| part.filter(|value| !value.is_empty()))
+        .collect();
+
+    if known.is_empty() { None } else { Some(known.join(".")) }
+}
+
+/// Detect the rendering engine from engine tokens in the user agent
+fn detect_engine(user_agent: &str, browser: &str, os: &str) -> Option<&'static str> {
+    // Every browser on iOS is required to use WebKit
+    if os == "iOS" {
+        return Some("WebKit");
+    }
+
+    if user_agent.contains("Edge/") {
+        Some("EdgeHTML")
+    } else if user_agent.contains("Trident/") || user_agent.contains("MSIE ") {
+        Some("Trident")
+    } else if user_agent.contains("Presto/") {
+        Some("Presto")
+    } else if user_agent.contains("Chrome/") || user_agent.contains("Chromium/") || browser.contains("Chrome") {
+        // Chromium forked WebKit into Blink, but still sends the AppleWebKit token
+        Some("Blink")
+    } else if user_agent.contains("AppleWebKit/") {
+        Some("WebKit")
+    } else if user_agent.contains("Gecko/") && !user_agent.contains("like Gecko") {
+        Some("Gecko")
+    } else {
+        None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tablet_token_does_not_match_windows_tablet_pc() {
+        let windows = "Mozilla/4.0 (compatible; MSIE 8.0; Windows NT 6.1; Trident/4.0; Tablet PC 2.0)";
+        assert_eq!(classify_device(windows, "Windows", None, None), Some(UaDeviceClass::Desktop));
+
+        let firefox_tablet = "Mozilla/5.0 (Android 13; Tablet; rv:120.0) Gecko/120.0 Firefox/120.0";
+        assert_eq!(classify_device(firefox_tablet, "Android", None, None), Some(UaDeviceClass::Tablet));
+    }
+}
@@ -0,0 +1,7 @@
+/// protobuf crate version
+pub const VERSION: &'static str = "3.7.2";
+/// This symbol is used by codegen
+#[doc(hidden)]
+pub const VERSION_IDENT: &'static str = "VERSION_3_7_2";
+/// This symbol can be referenced to assert that proper version of crate is used
+pub const VERSION_3_7_2: () = ();
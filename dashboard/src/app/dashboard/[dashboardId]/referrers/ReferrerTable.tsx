@@ -15,7 +15,9 @@ export const ReferrerTab = {
   Social: 'social',
   Direct: 'direct',
   Email: 'email',
+  AI: 'ai',
   Other: 'other',
+  Spam: 'spam',
 } as const;
 
 export type ReferrerTabKey = (typeof ReferrerTab)[keyof typeof ReferrerTab];
@@ -4,6 +4,8 @@ export const REFERRER_COLORS: Record<string, string> = {
   'social': '#8B5CF6', // Violet
   'direct': '#10B981', // Emerald
   'email': '#EF4444',  // Red
+  'ai': '#14B8A6',     // Teal
+  'spam': '#6B7280',   // Gray
   'other': '#F59E0B',  // Amber
 };
 
@@ -0,0 +1,8 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS subdivision_code Nullable(String),
+    ADD COLUMN IF NOT EXISTS city Nullable(String),
+    ADD COLUMN IF NOT EXISTS timezone Nullable(String);
+
+ALTER TABLE analytics.events
+    ADD INDEX subdivision_code_idx subdivision_code TYPE bloom_filter GRANULARITY 3,
+    ADD INDEX city_idx city TYPE bloom_filter GRANULARITY 3;
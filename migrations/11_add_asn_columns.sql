@@ -0,0 +1,6 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS asn Nullable(UInt32),
+    ADD COLUMN IF NOT EXISTS asn_organization Nullable(String);
+
+ALTER TABLE analytics.events
+    ADD INDEX asn_organization_idx asn_organization TYPE bloom_filter GRANULARITY 3;
@@ -0,0 +1,6 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS device_brand String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS device_model String DEFAULT '';
+
+ALTER TABLE analytics.events
+    ADD INDEX device_brand_idx device_brand TYPE bloom_filter GRANULARITY 3;
@@ -0,0 +1,4 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS browser_version String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS os_version String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS browser_engine String DEFAULT '';
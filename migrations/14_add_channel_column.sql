@@ -0,0 +1,5 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS channel String DEFAULT '';
+
+ALTER TABLE analytics.events
+    ADD INDEX channel_idx channel TYPE bloom_filter GRANULARITY 3;
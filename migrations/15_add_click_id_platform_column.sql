@@ -0,0 +1,2 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS click_id_platform String DEFAULT '';
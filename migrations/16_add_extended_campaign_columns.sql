@@ -0,0 +1,5 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS utm_id String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS utm_source_platform String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS utm_creative_format String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS utm_marketing_tactic String DEFAULT '';
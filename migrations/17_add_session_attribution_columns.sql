@@ -0,0 +1,9 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS session_referrer_source String DEFAULT 'direct',
+    ADD COLUMN IF NOT EXISTS session_referrer_source_name String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS session_referrer_url String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS session_utm_source String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS session_utm_medium String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS session_utm_campaign String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS session_utm_term String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS session_utm_content String DEFAULT '';
@@ -0,0 +1,3 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS custom_event_properties Map(String, String),
+    ADD COLUMN IF NOT EXISTS custom_event_numeric_properties Map(String, Float64);
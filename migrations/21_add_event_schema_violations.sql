@@ -0,0 +1,15 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS schema_violations Array(String) DEFAULT [];
+
+CREATE TABLE IF NOT EXISTS analytics.event_schema_violations (
+    site_id String,
+    timestamp DateTime,
+    date Date DEFAULT toDate(timestamp),
+    event_name String,
+    violation String,
+    property String,
+    detail String,
+    action String
+) ENGINE = MergeTree()
+PARTITION BY toYYYYMM(date)
+ORDER BY (site_id, date, event_name, timestamp);
@@ -0,0 +1,12 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS revenue Decimal(18, 4) DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS revenue_currency String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS revenue_reporting Nullable(Decimal(18, 4)),
+    ADD COLUMN IF NOT EXISTS reporting_currency String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS order_id String DEFAULT '',
+    ADD COLUMN IF NOT EXISTS revenue_items Nested(
+        id String,
+        name String,
+        quantity UInt32,
+        price Decimal(18, 4)
+    );
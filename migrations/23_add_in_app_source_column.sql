@@ -0,0 +1,2 @@
+ALTER TABLE analytics.events
+    ADD COLUMN IF NOT EXISTS in_app_source String DEFAULT '';
@@ -0,0 +1,35 @@
+-- One row per order, so repeated purchase events (e.g. after a restart cleared the in-memory dedup cache) are counted once.
+-- When the same order arrives again, the earliest event is kept: ReplacingMergeTree keeps the row with the highest
+-- version, and the version decreases with the event time. Repeats within the same second keep either row.
+-- Not partitioned, since ReplacingMergeTree only collapses rows within a partition. Query with FINAL.
+CREATE TABLE IF NOT EXISTS analytics.orders (
+    site_id String,
+    order_id String,
+    timestamp DateTime,
+    visitor_id String,
+    session_id String,
+    channel String,
+    revenue Decimal(18, 4),
+    revenue_currency String,
+    revenue_reporting Nullable(Decimal(18, 4)),
+    reporting_currency String,
+    version UInt32
+) ENGINE = ReplacingMergeTree(version)
+ORDER BY (site_id, order_id);
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS analytics.orders_mv
+TO analytics.orders
+AS SELECT
+    site_id,
+    order_id,
+    timestamp,
+    visitor_id,
+    session_id,
+    channel,
+    revenue,
+    revenue_currency,
+    revenue_reporting,
+    reporting_currency,
+    toUInt32(4294967295 - toUnixTimestamp(timestamp)) AS version
+FROM analytics.events
+WHERE order_id != '';
@@ -6,6 +6,8 @@
     document.querySelector('script[src*="analytics.js"]');
   var siteId = script.getAttribute("data-site-id");
   var serverUrl = script.getAttribute("data-server-url");
+  // Opt-in for single page apps that route with the URL fragment, e.g. "#/settings"
+  var hashRouting = script.hasAttribute("data-hash-routing");
   var urlPatterns =
     script
       .getAttribute("data-dynamic-urls")
@@ -28,8 +30,60 @@
     return console.error("Betterlytics: data-server-url attribute missing");
   }
 
-  // Track current path for SPA navigation
-  var currentPath = window.location.pathname;
+  // Cross-domain tracking: links to these domains carry a signed token that continues the visitor and session
+  var crossDomains = (script.getAttribute("data-cross-domain") || "")
+    .split(",")
+    .map(function (d) {
+      return d.trim().toLowerCase();
+    })
+    .filter(Boolean);
+  var tokenUrl =
+    script.getAttribute("data-cross-domain-url") ||
+    serverUrl.replace(/\/track\/?$/, "/cross-domain/token");
+  var tokenParam = "_bl";
+  var incomingToken = null;
+  var linkToken = null;
+  var linkTokenRequest = null;
+
+  // Pick up a token from another domain and remove it from the address bar
+  var currentUrl = new URL(window.location.href);
+  if (currentUrl.searchParams.has(tokenParam)) {
+    incomingToken = currentUrl.searchParams.get(tokenParam);
+    currentUrl.searchParams.delete(tokenParam);
+    window.history.replaceState(window.history.state, "", currentUrl.toString());
+  }
+
+  // Collect User-Agent Client Hints where supported, since reduced user agents hide OS and device details
+  var clientHints = null;
+  var hintsReady = Promise.resolve();
+  var uaData = navigator.userAgentData;
+  if (uaData) {
+    clientHints = {
+      brands: (uaData.brands || []).map(function (b) {
+        return { brand: b.brand, version: b.version };
+      }),
+      platform: uaData.platform || null,
+      mobile: uaData.mobile,
+    };
+    if (uaData.getHighEntropyValues) {
+      hintsReady = uaData
+        .getHighEntropyValues(["platformVersion", "model"])
+        .then(function (values) {
+          clientHints.platform_version = values.platformVersion || null;
+          clientHints.model = values.model || null;
+        })
+        .catch(function () {});
+    }
+  }
+
+  // Track current path for SPA navigation, including hash routes such as "#/settings" with data-hash-routing
+  function currentRoute() {
+    var hash = window.location.hash;
+    var isHashRoute = hashRouting && (hash.indexOf("#/") === 0 || hash.indexOf("#!/") === 0);
+    return window.location.pathname + (isHashRoute ? hash.split("?")[0] : "");
+  }
+
+  var currentPath = currentRoute();
 
   function normalize(url) {
     var urlObj = new URL(url);
@@ -47,7 +101,7 @@
     return url;
   }
 
-  function trackEvent(eventName, isCustomEvent = false, properties = {}) {
+  function buildPayload(eventName, isCustomEvent, properties, revenue) {
     var url = normalize(window.location.href);
     var referrer = document.referrer || null;
     var userAgent = navigator.userAgent;
@@ -64,34 +118,112 @@
       return Math.abs(hash).toString(16);
     })(userAgent + screenResolution);
 
-    // Send tracking data
-    fetch(serverUrl, {
-      method: "POST",
-      keepalive: true,
-      headers: {
-        "Content-Type": "application/json",
-      },
-      body: JSON.stringify({
-        site_id: siteId,
-        event_name: eventName,
-        is_custom_event: isCustomEvent,
-        properties: JSON.stringify(properties),
-        url: url,
-        referrer: referrer,
-        user_agent: userAgent,
-        screen_resolution: screenResolution,
-        visitor_id: visitorId,
-        timestamp: Math.floor(Date.now() / 1000),
-      }),
+    return {
+      site_id: siteId,
+      event_name: eventName,
+      is_custom_event: isCustomEvent,
+      properties: JSON.stringify(properties),
+      url: url,
+      title: document.title || null,
+      referrer: referrer,
+      user_agent: userAgent,
+      screen_resolution: screenResolution,
+      visitor_id: visitorId,
+      timestamp: Math.floor(Date.now() / 1000),
+      client_hints: clientHints,
+      cross_domain_token: incomingToken,
+      revenue: revenue || null,
+    };
+  }
+
+  function trackEvent(eventName, isCustomEvent = false, properties = {}, revenue = null) {
+    var payload = buildPayload(eventName, isCustomEvent, properties, revenue);
+
+    // Send tracking data once client hints are available
+    hintsReady.then(function () {
+      payload.client_hints = clientHints;
+      return fetch(serverUrl, {
+        method: "POST",
+        keepalive: true,
+        headers: {
+          "Content-Type": "application/json",
+        },
+        body: JSON.stringify(payload),
+      });
+    }).then(function (response) {
+      // The server links the visitor on the first accepted event, so the token is only sent until then
+      if (response.ok && payload.cross_domain_token === incomingToken) {
+        incomingToken = null;
+      }
+      // Link tokens are only issued for an active session, so request one once an event has been accepted
+      if (response.ok && crossDomains.length > 0 && !linkToken) {
+        refreshLinkToken();
+      }
     }).catch(function (error) {
       console.error("Analytics tracking failed:", error);
     });
   }
 
+  // Fetch a fresh link token and renew it halfway through its lifetime
+  function refreshLinkToken() {
+    if (linkTokenRequest) return;
+    linkTokenRequest = hintsReady
+      .then(function () {
+        return fetch(tokenUrl, {
+          method: "POST",
+          headers: {
+            "Content-Type": "application/json",
+          },
+          body: JSON.stringify(buildPayload("cross_domain_token", false, {})),
+        });
+      })
+      .then(function (response) {
+        return response.ok ? response.json() : null;
+      })
+      .then(function (data) {
+        if (!data) return;
+        linkToken = data.token;
+        setTimeout(function () {
+          linkToken = null;
+          refreshLinkToken();
+        }, (data.expires_in * 1000) / 2);
+      })
+      .catch(function (error) {
+        console.error("Cross-domain token request failed:", error);
+      })
+      .finally(function () {
+        linkTokenRequest = null;
+      });
+  }
+
+  function isCrossDomain(hostname) {
+    hostname = hostname.toLowerCase();
+    if (hostname === window.location.hostname.toLowerCase()) return false;
+    return crossDomains.some(function (domain) {
+      return hostname === domain || hostname.endsWith("." + domain);
+    });
+  }
+
+  function decorateLink(event) {
+    var link = event.target.closest && event.target.closest("a[href]");
+    if (!link || !linkToken) return;
+    var linkUrl = new URL(link.href, window.location.href);
+    if (!isCrossDomain(linkUrl.hostname)) return;
+    linkUrl.searchParams.set(tokenParam, linkToken);
+    link.href = linkUrl.toString();
+  }
+
+  if (crossDomains.length > 0) {
+    document.addEventListener("mousedown", decorateLink, true);
+    document.addEventListener("keydown", decorateLink, true);
+    document.addEventListener("touchstart", decorateLink, true);
+  }
+
   var queuedEvents = (window.betterlytics && window.betterlytics.q) || [];
 
-  window.betterlytics = (eventName, eventProps = {}) =>
-    trackEvent(eventName, true, eventProps);
+  // Revenue is optional: { amount, currency, order_id, items: [{ id, name, quantity, price }] }
+  window.betterlytics = (eventName, eventProps = {}, revenue = null) =>
+    trackEvent(eventName, true, eventProps, revenue);
 
   // DEPRECATED: Remains temporarily for backwards compatibility
   window.baEvent = (eventName, eventProps = {}) =>
@@ -117,16 +249,26 @@
     var originalPushState = history.pushState;
     history.pushState = function () {
       originalPushState.apply(this, arguments);
-      if (currentPath !== window.location.pathname) {
-        currentPath = window.location.pathname;
+      if (currentPath !== currentRoute()) {
+        currentPath = currentRoute();
         trackEvent("pageview");
       }
     };
 
     // Track popstate (back/forward navigation)
     window.addEventListener("popstate", function () {
-      if (currentPath !== window.location.pathname) {
-        currentPath = window.location.pathname;
+      if (currentPath !== currentRoute()) {
+        currentPath = currentRoute();
+        trackEvent("pageview");
+      }
+    });
+  }
+
+  // Track hash-routed navigation
+  if (hashRouting) {
+    window.addEventListener("hashchange", function () {
+      if (currentPath !== currentRoute()) {
+        currentPath = currentRoute();
         trackEvent("pageview");
       }
     });